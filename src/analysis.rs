@@ -1,362 +1,4160 @@
-use std::fmt::Display;
+use std::{fmt::Display, path::PathBuf};
 
-use tower_lsp::lsp_types::{self, Position, Range};
-use tracing::info;
+use lsp_types::{
+    self, DiagnosticSeverity, DocumentSymbol, Position, Range, SemanticTokenType, SymbolKind,
+    TextEdit,
+};
+use regex::Regex;
+use serde::Serialize;
+use tracing::{info, warn};
+use unicode_segmentation::UnicodeSegmentation as _;
 
 use crate::{
-    config::{self, CommitElementDefinition},
+    config::{
+        self, BreakingChangeCheck, CommitElementDefinition, FailureThreshold, HeaderStyle, Severity,
+    },
+    patterns::compile_user_pattern,
     regex,
 };
 
+#[derive(Clone)]
 pub struct State {
     config: config::Repository,
+    reference_pattern: Regex,
+    staged_files: Vec<PathBuf>,
 
+    text: String,
     lines: Vec<String>,
+    line_ending: LineEnding,
+    encoding: PositionEncoding,
+
+    result: AnalysisResult,
+}
+
+/// The elements parsed from a commit message.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnalysisResult {
+    /// The commit type (e.g. `feat`, `fix`)
+    pub ty: Option<Range>,
+    /// The commit scope, without the surrounding parentheses
+    pub scope: Option<Range>,
+    /// The `!` marking a breaking change in the header
+    pub breaking: Option<Range>,
+    /// The description following the `type(scope): ` prefix
+    pub subject: Option<Range>,
+    /// The token of the `BREAKING CHANGE: ` footer
+    pub breaking_change_footer: Option<Range>,
+    /// All references to tickets in the commit text
+    pub references: Vec<Reference>,
+    /// All references to merge requests (e.g. `!123`), if enabled in the config
+    pub merge_requests: Vec<Reference>,
+    /// The git trailers (e.g. `Signed-off-by: `) at the end of the message
+    pub trailers: Vec<Trailer>,
+    /// Problems encountered while parsing
+    pub errors: Vec<ParseError>,
+    /// Whether git generated the header of the commit
+    pub kind: CommitKind,
+    /// The emoji or `:shortcode:` before the type, if gitmoji headers are enabled
+    pub gitmoji: Option<Range>,
+}
+
+/// Commits whose header is written by git and does not follow the conventional format.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum CommitKind {
+    #[default]
+    Regular,
+    /// `Merge branch 'feature'`, `Merge pull request #1 from ...`, etc.
+    Merge,
+    /// `Revert "feat: thing"`, with the range of the subject of the reverted commit
+    Revert { reverted: Range },
+    /// `fixup! feat: thing`, `squash! ...` or `amend! ...`, as written by `git commit --fixup`.
+    /// The rest of the header is the subject of the target commit.
+    Autosquash,
+}
+
+/// A reference to a ticket/issue/etc. in the commit text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    /// The reference as written in the text (e.g. `#123`)
+    pub text: String,
+    /// The key identifying the ticket (e.g. `123` for `#123` or `PROJ-123`)
+    pub key: String,
+    pub range: Range,
+    /// Whether the reference follows one of the `closing_keywords` (e.g. `Closes #123`), as
+    /// opposed to merely mentioning the ticket (e.g. `See #123`).
+    pub closing: bool,
+    /// The repository of a cross-repository reference like `owner/repo#123`, `None` for tickets
+    /// of the current repository.
+    pub repo: Option<String>,
+}
+
+impl Reference {
+    /// The numeric ID of the ticket, for trackers that use numbers as keys.
+    ///
+    /// `None` for references into other repositories, which the issue tracker of the current
+    /// repository does not know.
+    pub fn id(&self) -> Option<u64> {
+        if self.repo.is_some() {
+            return None;
+        }
+        self.key.parse().ok()
+    }
+}
+
+/// A `Key: value` line in the trailer block at the end of a commit message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trailer {
+    pub key: Range,
+    /// The value, including continuation lines
+    pub value: Range,
+}
+
+/// A part of the commit message that could not be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub range: Range,
+    pub message: String,
+}
+
+impl AnalysisResult {
+    /// Parse the lines of a commit message.
+    ///
+    /// `reference_pattern` matches references to tickets, see
+    /// [`config::Repository::issue_reference_pattern`].
+    pub fn parse(lines: &[String], reference_pattern: &Regex) -> Self {
+        Self::parse_with_style(
+            lines,
+            reference_pattern,
+            HeaderStyle::Conventional,
+            PositionEncoding::Utf32,
+        )
+    }
+
+    /// Like [`Self::parse`], but the header may start with a gitmoji, depending on `style`, and
+    /// positions count units of `encoding`.
+    pub fn parse_with_style(
+        lines: &[String],
+        reference_pattern: &Regex,
+        style: HeaderStyle,
+        encoding: PositionEncoding,
+    ) -> Self {
+        let mut references = find_references(lines, reference_pattern, encoding);
+        add_repo_prefixes(&mut references, lines, encoding);
+        let mut result = Self {
+            references,
+            breaking_change_footer: find_breaking_change_footer(lines),
+            trailers: find_trailers(lines, encoding),
+            ..Default::default()
+        };
+
+        let Some(header) = lines.first() else {
+            return result;
+        };
+
+        if header.starts_with("Merge ") {
+            result.kind = CommitKind::Merge;
+        } else if let Some(reverted) = regex!(r#"^Revert "(.+)"$"#)
+            .captures(header)
+            .and_then(|c| c.get(1))
+        {
+            result.kind = CommitKind::Revert {
+                reverted: encoding.line_range(0, header, reverted.range()),
+            };
+        }
+
+        let mut conventional = header.as_str();
+        if let Some(prefix) = regex!(r"^(?:(?:fixup|squash|amend)! )+").find(header) {
+            result.kind = CommitKind::Autosquash;
+            conventional = &header[prefix.end()..];
+        }
+        if style == HeaderStyle::Gitmoji {
+            if let Some((gitmoji, rest)) = split_gitmoji(conventional) {
+                let offset = header.len() - conventional.len();
+                result.gitmoji = Some(encoding.line_range(
+                    0,
+                    header,
+                    gitmoji.start + offset..gitmoji.end + offset,
+                ));
+                conventional = rest;
+            }
+        }
+        let prefix_len = header.len() - conventional.len();
+
+        let problem = diagnose_header(conventional).map(|(range, message)| ParseError {
+            range: encoding.line_range(0, header, range.start + prefix_len..range.end + prefix_len),
+            message: message.to_owned(),
+        });
+
+        match parse_header(conventional) {
+            Some((ty, scope, breaking, subject)) => {
+                result.ty = Some(encoding.line_range(0, header, substr_offset(header, ty)));
+                result.scope =
+                    scope.map(|txt| encoding.line_range(0, header, substr_offset(header, txt)));
+                result.breaking = breaking.then(|| {
+                    let end = substr_offset(header, subject).start - ": ".len();
+                    encoding.line_range(0, header, end - 1..end)
+                });
+                result.subject =
+                    Some(encoding.line_range(0, header, substr_offset(header, subject)));
+                // e.g. more than one space after the colon
+                result.errors.extend(problem);
+            }
+            None if !header.trim().is_empty() => {
+                result.errors.push(problem.unwrap_or_else(|| ParseError {
+                    range: encoding.line_range(0, header, 0..header.len()),
+                    message: "The header should have the format `type(scope): subject`".to_owned(),
+                }))
+            }
+            None => {}
+        }
 
-    ty: Option<Range>,
-    scope: Option<Range>,
+        result
+    }
 }
 
 impl State {
     pub fn new(config: config::Repository) -> Self {
         Self {
+            reference_pattern: reference_pattern(&config),
             config,
+            staged_files: Vec::new(),
+            text: String::new(),
             lines: Vec::new(),
-            ty: None,
-            scope: None,
+            line_ending: LineEnding::default(),
+            encoding: PositionEncoding::default(),
+            result: AnalysisResult::default(),
+        }
+    }
+
+    /// Replace the configuration, e.g. after the config file changed, and analyse the current
+    /// text again.
+    pub fn set_config(&mut self, config: config::Repository) {
+        self.reference_pattern = reference_pattern(&config);
+        self.config = config;
+        self.parse();
+    }
+
+    /// Count the characters of positions in `encoding`, and analyse the current text again.
+    pub fn set_position_encoding(&mut self, encoding: PositionEncoding) {
+        self.encoding = encoding;
+        self.parse();
+    }
+
+    /// Set the files staged for the commit, relative to the repository root.
+    pub fn set_staged_files(&mut self, staged_files: Vec<PathBuf>) {
+        self.staged_files = staged_files;
+    }
+
+    pub fn update_text(&mut self, new_text: &str) -> &AnalysisResult {
+        self.text = new_text.to_owned();
+        self.lines = new_text.lines().map(ToOwned::to_owned).collect();
+        self.line_ending = LineEnding::detect(new_text);
+        self.parse();
+
+        &self.result
+    }
+
+    fn parse(&mut self) {
+        self.result = AnalysisResult::parse_with_style(
+            &self.lines,
+            &self.reference_pattern,
+            self.config.header_style,
+            self.encoding,
+        );
+        mark_closing_references(
+            &mut self.result.references,
+            &self.lines,
+            &self.config.closing_keywords,
+            self.encoding,
+        );
+        if self.config.merge_request_references {
+            self.result.merge_requests =
+                find_references(&self.lines, merge_request_pattern(), self.encoding);
+        }
+    }
+
+    /// Replace the text in `range` with `new_text` and parse the result.
+    pub fn apply_change(&mut self, range: Range, new_text: &str) -> &AnalysisResult {
+        let start = self.offset_of(range.start);
+        let end = self.offset_of(range.end).max(start);
+
+        let mut text = std::mem::take(&mut self.text);
+        text.replace_range(start..end, new_text);
+        self.update_text(&text)
+    }
+
+    /// Convert a position into an offset into the full text.
+    /// Positions past the end of a line or the text are clamped to that end.
+    fn offset_of(&self, pos: Position) -> usize {
+        let mut line_start = 0;
+        for _ in 0..pos.line {
+            match self.text[line_start..].find('\n') {
+                Some(idx) => line_start += idx + 1,
+                None => return self.text.len(),
+            }
+        }
+
+        let line = &self.text[line_start..];
+        let line = &line[..line.find(['\r', '\n']).unwrap_or(line.len())];
+        line_start
+            + self
+                .encoding
+                .byte_offset(line, pos.character)
+                .unwrap_or(line.len())
+    }
+
+    /// The elements parsed from the current text.
+    pub fn result(&self) -> &AnalysisResult {
+        &self.result
+    }
+
+    pub fn all_diagnostics(&self) -> Vec<Diagnostic> {
+        let lint_header = match self.result.kind {
+            CommitKind::Regular => true,
+            CommitKind::Merge if self.config.lint_merge_commits => true,
+            // the whole message of a merge commit is usually generated by git
+            CommitKind::Merge => return Vec::new(),
+            CommitKind::Revert { .. } => false,
+            // the header was linted when the target commit was written
+            CommitKind::Autosquash => false,
+        };
+
+        let mut diagnostics = Vec::new();
+
+        if lint_header {
+            diagnostics.extend(self.check_header_format());
+        }
+
+        diagnostics.extend(self.check_body_separator());
+
+        if lint_header {
+            if let Some(diag) = self.check_subject_length() {
+                diagnostics.push(diag);
+            }
+        }
+
+        diagnostics.extend(self.check_body_line_length());
+
+        if lint_header {
+            if let Some(diag) = self.check_commit_type() {
+                diagnostics.push(diag);
+            }
+
+            if let Some(diag) = self.check_commit_scope() {
+                diagnostics.push(diag);
+            }
+
+            if let Some(diag) = self.check_scope_matches_staged() {
+                diagnostics.push(diag);
+            }
+
+            if let Some(diag) = self.check_empty_subject() {
+                diagnostics.push(diag);
+            }
+
+            if let Some(diag) = self.check_body_required() {
+                diagnostics.push(diag);
+            }
+
+            if let Some(diag) = self.check_scope_required() {
+                diagnostics.push(diag);
+            }
+
+            if let Some(diag) = self.check_trailing_period() {
+                diagnostics.push(diag);
+            }
+
+            if let Some(diag) = self.check_imperative_mood() {
+                diagnostics.push(diag);
+            }
+        }
+
+        if let Some(diag) = self.check_breaking_change() {
+            diagnostics.push(diag);
+        }
+
+        diagnostics.extend(self.check_sign_off());
+        diagnostics.extend(self.check_trailers());
+        if lint_header {
+            diagnostics.extend(self.check_ascii_subject());
+        }
+        diagnostics.extend(self.check_reference_keywords());
+
+        self.configure_rules(diagnostics)
+    }
+
+    /// Remove the diagnostics of disabled rules and apply the configured severities.
+    fn configure_rules(&self, mut diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        let code = |diag: &Diagnostic| match &diag.inner.code {
+            Some(lsp_types::NumberOrString::String(code)) => Some(code.clone()),
+            _ => None,
+        };
+
+        diagnostics.retain(|d| code(d).is_none_or(|c| !self.config.disabled_rules.contains(&c)));
+        for diag in &mut diagnostics {
+            if let Some(&severity) = code(diag).and_then(|c| self.config.severities.get(&c)) {
+                diag.inner.severity = Some(severity.into());
+            }
+        }
+        diagnostics
+    }
+
+    /// Warn about references to tickets that do not exist, according to `is_missing`.
+    ///
+    /// Looking up the tickets needs the issue tracker, so this is not part of
+    /// [`Self::all_diagnostics`]. Does nothing unless `check_references_exist` is enabled.
+    pub fn check_references_exist(&self, is_missing: impl Fn(u64) -> bool) -> Vec<Diagnostic> {
+        if !self.config.check_references_exist {
+            return Vec::new();
+        }
+
+        let diagnostics = self
+            .references()
+            .filter(|r| r.id().is_some_and(&is_missing))
+            .map(|r| {
+                Diagnostic::new(r.range, format!("Ticket {} does not exist", r.text))
+                    .with_severity(DiagnosticSeverity::WARNING)
+                    .with_code(rule::MISSING_TICKET)
+            })
+            .collect();
+        self.configure_rules(diagnostics)
+    }
+
+    /// Check that the commit type is one of the configured types.
+    /// Does nothing if no types are configured, the default types are not enforced.
+    fn check_commit_type(&self) -> Option<Diagnostic> {
+        let range = self.result.ty?;
+        if self.config.types.is_empty()
+            || self.config.types_are_defaults
+            || self.commit_type_info().is_some()
+        {
+            return None;
+        }
+
+        let expected: Vec<_> = self.config.types.iter().map(|t| t.name.as_str()).collect();
+        Some(
+            Diagnostic::new(
+                range,
+                format!(
+                    "Unknown commit type '{}', expected one of: {}",
+                    self.get_text(range),
+                    expected.join(", ")
+                ),
+            )
+            .with_severity(DiagnosticSeverity::WARNING)
+            .with_code(rule::UNKNOWN_TYPE),
+        )
+    }
+
+    /// Check that the commit scope is one of the configured scopes.
+    /// Does nothing if the commit has no scope or no scopes are configured.
+    fn check_commit_scope(&self) -> Option<Diagnostic> {
+        let range = self.result.scope?;
+        if self.config.scopes.is_empty() || self.commit_scope_info().is_some() {
+            return None;
+        }
+
+        let expected: Vec<_> = self.config.scopes.iter().map(|s| s.name.as_str()).collect();
+        Some(
+            Diagnostic::new(
+                range,
+                format!(
+                    "Unknown commit scope '{}', expected one of: {}",
+                    self.get_text(range),
+                    expected.join(", ")
+                ),
+            )
+            .with_severity(DiagnosticSeverity::WARNING)
+            .with_code(rule::UNKNOWN_SCOPE),
+        )
+    }
+
+    /// Warn if the declared scope has `paths` configured, but none of the staged files is
+    /// within them. Does nothing if nothing is staged.
+    fn check_scope_matches_staged(&self) -> Option<Diagnostic> {
+        if !self.config.check_scope_matches_staged || self.staged_files.is_empty() {
+            return None;
+        }
+        let range = self.result.scope?;
+        let scope = self.commit_scope_info()?;
+        if scope.paths.is_empty() {
+            return None;
+        }
+
+        let matches = scope
+            .paths
+            .iter()
+            .any(|path| self.staged_files.iter().any(|f| f.starts_with(path)));
+        if matches {
+            return None;
+        }
+
+        let paths: Vec<_> = scope
+            .paths
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect();
+        Some(
+            Diagnostic::new(
+                range,
+                format!(
+                    "None of the staged files belongs to scope '{}' ({})",
+                    scope.name,
+                    paths.join(", ")
+                ),
+            )
+            .with_severity(DiagnosticSeverity::WARNING)
+            .with_code(rule::SCOPE_NOT_STAGED),
+        )
+    }
+
+    /// Warn about a header like `feat: ` without a subject.
+    fn check_empty_subject(&self) -> Option<Diagnostic> {
+        let range = self.result.subject?;
+        if !self.get_text(range).trim().is_empty() {
+            return None;
+        }
+
+        // include the `: ` before the subject, so that the range is not empty
+        let start = Position::new(range.start.line, range.start.character.saturating_sub(2));
+        Some(
+            Diagnostic::new(Range::new(start, range.end), "The subject is empty")
+                .with_severity(DiagnosticSeverity::WARNING)
+                .with_code(rule::EMPTY_SUBJECT),
+        )
+    }
+
+    /// Warn if the commit type is one of `types_requiring_body`, but the message has no body.
+    fn check_body_required(&self) -> Option<Diagnostic> {
+        let ty = self.get_text(self.result.ty?);
+        if !self.config.types_requiring_body.contains(&ty) || self.body_lines().next().is_some() {
+            return None;
+        }
+
+        Some(
+            Diagnostic::new(
+                self.full_line(0),
+                format!("Commits of type '{ty}' should have a body explaining the change"),
+            )
+            .with_severity(DiagnosticSeverity::WARNING)
+            .with_code(rule::MISSING_BODY),
+        )
+    }
+
+    /// Warn if the commit type is one of `types_requiring_scope`, but no scope is given.
+    ///
+    /// The diagnostic covers the header up to the colon, where the scope would be inserted.
+    fn check_scope_required(&self) -> Option<Diagnostic> {
+        if self.config.types_requiring_scope.is_empty() || self.result.scope.is_some() {
+            return None;
+        }
+        let ty = self.get_text(self.result.ty?);
+        if !self.config.types_requiring_scope.contains(&ty) {
+            return None;
+        }
+
+        let header = self.lines.first()?;
+        let type_start = self
+            .encoding
+            .byte_offset(header, self.result.ty?.start.character)?;
+        let colon = type_start + header[type_start..].find(':')?;
+        Some(
+            Diagnostic::new(
+                self.encoding.line_range(0, header, 0..colon),
+                format!("Commits of type '{ty}' need a scope, e.g. '{ty}(scope):'"),
+            )
+            .with_severity(DiagnosticSeverity::WARNING)
+            .with_code(rule::MISSING_SCOPE),
+        )
+    }
+
+    /// Warn if the subject ends with a period.
+    ///
+    /// An ellipsis (`...`) is not considered a trailing period, as it is used deliberately to
+    /// indicate that the subject continues in the body.
+    fn check_trailing_period(&self) -> Option<Diagnostic> {
+        let range = self.result.subject?;
+        let subject = self.get_text(range);
+
+        if !subject.ends_with('.') || subject.ends_with("..") {
+            return None;
+        }
+
+        let end = range.end;
+        Some(
+            Diagnostic::new(
+                Range::new(Position::new(end.line, end.character - 1), end),
+                "The subject should not end with a period",
+            )
+            .with_severity(DiagnosticSeverity::WARNING)
+            .with_code(rule::TRAILING_PERIOD),
+        )
+    }
+
+    /// Warn if the first word of the subject looks like it is not in imperative mood
+    /// (e.g. "fixed" or "adding" instead of "fix" or "add").
+    ///
+    /// This is only a heuristic based on common suffixes and thus disabled by default.
+    fn check_imperative_mood(&self) -> Option<Diagnostic> {
+        if !self.config.check_imperative_mood {
+            return None;
+        }
+
+        let range = self.result.subject?;
+        let subject = self.get_text(range);
+        let word = subject.split(|c: char| !c.is_alphabetic()).next()?;
+
+        if !is_non_imperative(word) {
+            return None;
+        }
+
+        let start = range.start.character;
+        Some(
+            Diagnostic::new(
+                Range::new(
+                    Position::new(range.start.line, start),
+                    Position::new(
+                        range.start.line,
+                        start + self.encoding.text_len(word) as u32,
+                    ),
+                ),
+                format!("Use the imperative mood in the subject (e.g. 'fix' instead of '{word}')"),
+            )
+            .with_severity(DiagnosticSeverity::WARNING)
+            .with_code(rule::IMPERATIVE_MOOD),
+        )
+    }
+
+    /// Check that a breaking change is marked consistently in header and footer.
+    fn check_breaking_change(&self) -> Option<Diagnostic> {
+        let check = self.config.breaking_change_check;
+        let marker = self.result.breaking;
+        let footer = self.result.breaking_change_footer;
+
+        let diag = match (marker, footer) {
+            (Some(marker), None)
+                if matches!(
+                    check,
+                    BreakingChangeCheck::Footer | BreakingChangeCheck::Both
+                ) =>
+            {
+                Diagnostic::new(
+                    marker,
+                    "Describe the breaking change in a `BREAKING CHANGE:` footer",
+                )
+            }
+            (None, Some(footer))
+                if matches!(
+                    check,
+                    BreakingChangeCheck::Marker | BreakingChangeCheck::Both
+                ) =>
+            {
+                Diagnostic::new(
+                    footer,
+                    "Mark the breaking change with a `!` before the colon in the header",
+                )
+            }
+            _ => return None,
+        };
+
+        Some(
+            diag.with_severity(DiagnosticSeverity::WARNING)
+                .with_code(rule::BREAKING_CHANGE),
+        )
+    }
+
+    /// Check for a `Signed-off-by` trailer with a `Name <email>` value, if required.
+    fn check_sign_off(&self) -> Vec<Diagnostic> {
+        if !self.config.require_sign_off || self.is_empty() {
+            return Vec::new();
         }
+
+        let identity_format = regex!(r"^[^<>]*[^<>\s] <[^<>@\s]+@[^<>\s]+>$");
+
+        let sign_offs: Vec<_> = self
+            .result
+            .trailers
+            .iter()
+            .filter(|t| self.get_text(t.key).eq_ignore_ascii_case("Signed-off-by"))
+            .collect();
+
+        if sign_offs.is_empty() {
+            let last_line = self
+                .lines
+                .iter()
+                .rposition(|l| !is_blank(l) && !is_comment(l))
+                .unwrap_or_default();
+
+            return vec![Diagnostic::new(
+                self.full_line(last_line as u32),
+                "Missing `Signed-off-by: Name <email>` trailer",
+            )
+            .with_code(rule::SIGN_OFF)];
+        }
+
+        sign_offs
+            .into_iter()
+            .filter(|t| !identity_format.is_match(&self.get_text(t.value)))
+            .map(|t| {
+                Diagnostic::new(
+                    t.value,
+                    "The sign-off should have the format `Name <email>`",
+                )
+                .with_code(rule::SIGN_OFF)
+            })
+            .collect()
+    }
+
+    /// Warn about trailers that repeat an earlier one, and about trailers naming a person
+    /// (like `Co-authored-by`) without an email address.
+    fn check_trailers(&self) -> Vec<Diagnostic> {
+        // `Signed-off-by` is checked more strictly by `check_sign_off` if it is required
+        let identity_keys: &[&str] = if self.config.require_sign_off {
+            &IDENTITY_TRAILERS[1..]
+        } else {
+            IDENTITY_TRAILERS
+        };
+        let email = regex!(r"<[^<>]*@[^<>]*>");
+
+        let mut seen = Vec::new();
+        let mut diagnostics = Vec::new();
+        for trailer in &self.result.trailers {
+            let key = self.get_text(trailer.key).to_lowercase();
+            let value = self.get_text(trailer.value).trim().to_owned();
+            let range = Range::new(trailer.key.start, trailer.value.end);
+
+            if identity_keys.contains(&key.as_str()) && !email.is_match(&value) {
+                diagnostics.push(
+                    Diagnostic::new(
+                        range,
+                        format!(
+                            "`{}` should name a person as `Name <email>`",
+                            self.get_text(trailer.key)
+                        ),
+                    )
+                    .with_severity(DiagnosticSeverity::WARNING)
+                    .with_code(rule::TRAILER_IDENTITY),
+                );
+            }
+
+            if seen.contains(&(key.clone(), value.clone())) {
+                diagnostics.push(
+                    Diagnostic::new(range, "This trailer is a duplicate of an earlier one")
+                        .with_severity(DiagnosticSeverity::WARNING)
+                        .with_code(rule::DUPLICATE_TRAILER),
+                );
+            } else {
+                seen.push((key, value));
+            }
+        }
+        diagnostics
+    }
+
+    /// Report each run of non-ASCII characters in the subject line, if enabled.
+    fn check_ascii_subject(&self) -> Vec<Diagnostic> {
+        let Some(subject) = self.lines.first() else {
+            return Vec::new();
+        };
+        if !self.config.ascii_only_subject {
+            return Vec::new();
+        }
+
+        // a gitmoji is never ASCII, but allowed if it was enabled
+        let gitmoji_end = self
+            .result
+            .gitmoji
+            .and_then(|g| self.encoding.byte_offset(subject, g.end.character))
+            .unwrap_or(0);
+
+        let mut runs: Vec<std::ops::Range<usize>> = Vec::new();
+        for (idx, c) in subject
+            .char_indices()
+            .filter(|&(idx, c)| !c.is_ascii() && idx >= gitmoji_end)
+        {
+            match runs.last_mut() {
+                Some(run) if run.end == idx => run.end += c.len_utf8(),
+                _ => runs.push(idx..idx + c.len_utf8()),
+            }
+        }
+
+        runs.into_iter()
+            .map(|run| {
+                Diagnostic::new(
+                    self.encoding.line_range(0, subject, run),
+                    "The subject line should only contain ASCII characters",
+                )
+                .with_severity(DiagnosticSeverity::WARNING)
+                .with_code(rule::NON_ASCII_SUBJECT)
+            })
+            .collect()
+    }
+
+    /// Warn about references that are not introduced by one of the configured footer keywords
+    /// (e.g. `Closes: #42` or `Refs #42`).
+    fn check_reference_keywords(&self) -> Vec<Diagnostic> {
+        let keywords = &self.config.reference_keywords;
+        if keywords.is_empty() {
+            return Vec::new();
+        }
+
+        self.references()
+            .filter(|Reference { range, .. }| {
+                let line = range.start.line;
+                let token = footer_token(&self.lines[line as usize]);
+                line == 0
+                    || !token.is_some_and(|t| keywords.iter().any(|k| k.eq_ignore_ascii_case(t)))
+            })
+            .map(|Reference { range, .. }| {
+                Diagnostic::new(
+                    *range,
+                    format!(
+                        "Reference should be in a footer starting with one of: {}",
+                        keywords.join(", ")
+                    ),
+                )
+                .with_severity(DiagnosticSeverity::WARNING)
+                .with_code(rule::REFERENCE_KEYWORD)
+            })
+            .collect()
+    }
+
+    /// Check that subject and body are separated by a blank line.
+    ///
+    /// Also warns if the blank line is followed by nothing but more blank lines. A subject
+    /// followed by a single (possibly whitespace only) line is fine, since git strips trailing
+    /// blank lines anyway. Git comments are ignored.
+    fn check_body_separator(&self) -> Option<Diagnostic> {
+        let separator = self.lines.get(1)?;
+
+        if !is_blank(separator) && !is_comment(separator) {
+            return Some(
+                Diagnostic::new(self.full_line(1), "The second line should be empty!")
+                    .with_code(rule::MISSING_BLANK_LINE),
+            );
+        }
+
+        let mut body = self
+            .lines
+            .iter()
+            .enumerate()
+            .skip(2)
+            .filter(|(_, l)| !is_comment(l))
+            .peekable();
+        body.peek()?;
+
+        if body.all(|(_, l)| is_blank(l)) {
+            return Some(
+                Diagnostic::new(
+                    self.full_line(1),
+                    "The body is empty, remove the blank lines or add a body",
+                )
+                .with_severity(DiagnosticSeverity::WARNING)
+                .with_code(rule::EMPTY_BODY),
+            );
+        }
+
+        None
+    }
+
+    /// Warn about a header that does not have the format `type(scope): subject`, as reported by
+    /// the [parser](AnalysisResult::errors).
+    fn check_header_format(&self) -> Vec<Diagnostic> {
+        self.result
+            .errors
+            .iter()
+            .map(|e| {
+                Diagnostic::new(e.range, &e.message)
+                    .with_severity(DiagnosticSeverity::WARNING)
+                    .with_code(rule::INVALID_HEADER)
+            })
+            .collect()
+    }
+
+    /// Warn about the part of the subject line exceeding the configured maximum length.
+    fn check_subject_length(&self) -> Option<Diagnostic> {
+        let limit = self.config.subject_max_length.filter(|&l| l > 0)?;
+        let subject = self.lines.first()?;
+
+        // Count grapheme clusters, so that multibyte characters are only counted once.
+        let (overflow_start, _) = subject.grapheme_indices(true).nth(limit)?;
+        let length = subject.graphemes(true).count();
+
+        Some(
+            Diagnostic::new(
+                self.encoding
+                    .line_range(0, subject, overflow_start..subject.len()),
+                format!("Subject line is {length} characters long, the limit is {limit}"),
+            )
+            .with_severity(DiagnosticSeverity::WARNING)
+            .with_code(rule::SUBJECT_TOO_LONG),
+        )
+    }
+
+    /// Warn about the part of body lines exceeding the configured maximum length.
+    fn check_body_line_length(&self) -> Vec<Diagnostic> {
+        let Some(limit) = self.config.body_max_line_length.filter(|&l| l > 0) else {
+            return Vec::new();
+        };
+
+        self.body_lines()
+            .filter_map(|idx| {
+                let line = &self.lines[idx];
+                let (overflow_start, _) = line.grapheme_indices(true).nth(limit)?;
+                let length = line.graphemes(true).count();
+                Some(
+                    Diagnostic::new(
+                        self.encoding
+                            .line_range(idx as u32, line, overflow_start..line.len()),
+                        format!("Body line is {length} characters long, the limit is {limit}"),
+                    )
+                    .with_severity(DiagnosticSeverity::WARNING)
+                    .with_code(rule::BODY_LINE_TOO_LONG),
+                )
+            })
+            .collect()
+    }
+
+    /// Determine which part of the header the cursor is in, while it is still being typed.
+    /// Returns `None` if the position is not in the header.
+    pub fn header_part(&self, pos: Position) -> Option<HeaderPart> {
+        if pos.line != 0 {
+            return None;
+        }
+        let line = self.lines.first().map(String::as_str).unwrap_or_default();
+        let mut before = &line[..self.encoding.byte_offset(line, pos.character)?];
+        if let Some(gitmoji) = self.result.gitmoji {
+            // the cursor has to be behind the gitmoji to type the rest of the header
+            let end = self.encoding.byte_offset(line, gitmoji.end.character)?;
+            before = before.get(end..)?.trim_start();
+        }
+
+        let part = match before.find([':', '(']) {
+            None => HeaderPart::Type,
+            Some(idx) if before[idx..].starts_with(':') => HeaderPart::Subject,
+            Some(idx) if before[idx..].contains([')', ':']) => HeaderPart::Subject,
+            Some(_) => HeaderPart::Scope,
+        };
+        Some(part)
+    }
+
+    /// The text typed after a `#` up to the cursor, to filter ticket completions.
+    /// Returns `None` if the cursor is not after a `#`.
+    pub fn ticket_query(&self, pos: Position) -> Option<String> {
+        let line = self.lines.get(pos.line as usize)?;
+        let before = &line[..self.encoding.byte_offset(line, pos.character)?];
+        let (_, query) = before.rsplit_once('#')?;
+        query
+            .chars()
+            .all(char::is_alphanumeric)
+            .then(|| query.to_owned())
+    }
+
+    /// Look at the given position in the text and return the element there.
+    /// Returns `None` for out of bounds accesses and if there is nothing special there.
+    pub fn lookup(&self, pos: Position) -> Option<Item> {
+        if let Some(range) = self
+            .result
+            .breaking_change_footer
+            .filter(|r| r.start <= pos && pos < r.end)
+        {
+            return Some(Item {
+                kind: ItemKind::BreakingChange,
+                text: self.get_text(range),
+                range,
+            });
+        }
+
+        if let Some(trailer) = self
+            .result
+            .trailers
+            .iter()
+            .find(|t| t.key.start <= pos && pos < t.key.end)
+        {
+            return Some(Item {
+                kind: ItemKind::Trailer,
+                text: self.get_text(trailer.key),
+                range: trailer.key,
+            });
+        }
+
+        let line = self.lines.get(pos.line as usize)?;
+        let cursor = self.encoding.byte_offset(line, pos.character)?;
+
+        if let Some(reference) = self
+            .references()
+            .find(|r| r.range.start <= pos && pos <= r.range.end)
+        {
+            return Some(Item {
+                kind: ItemKind::Ref(reference.clone()),
+                text: reference.text.clone(),
+                range: reference.range,
+            });
+        }
+
+        if let Some((id, mr)) = self
+            .result
+            .merge_requests
+            .iter()
+            .filter(|r| r.range.start <= pos && pos <= r.range.end)
+            .find_map(|r| Some((r.id()?, r)))
+        {
+            return Some(Item {
+                kind: ItemKind::MergeRequest(id),
+                text: mr.text.clone(),
+                range: mr.range,
+            });
+        }
+
+        if let Some(range) = self
+            .result
+            .breaking
+            .filter(|r| r.start <= pos && pos < r.end)
+        {
+            return Some(Item {
+                kind: ItemKind::Breaking,
+                text: self.get_text(range),
+                range,
+            });
+        }
+
+        if let Some(gitmoji) = self.result.gitmoji {
+            if gitmoji.start <= pos && pos < gitmoji.end {
+                return Some(Item {
+                    kind: ItemKind::Gitmoji,
+                    text: self.get_text(gitmoji),
+                    range: gitmoji,
+                });
+            }
+        }
+
+        if let CommitKind::Revert { reverted } = self.result.kind {
+            if reverted.start <= pos && pos <= reverted.end {
+                return Some(Item {
+                    kind: ItemKind::Revert,
+                    text: self.get_text(reverted),
+                    range: reverted,
+                });
+            }
+        }
+
+        // find word under cursor
+        let start = line[..cursor]
+            .rfind(|c: char| !c.is_alphanumeric() && c != '#')
+            .map(|i| i + 1)
+            .unwrap_or(0);
+
+        let end = line[cursor..]
+            .find(|c: char| !c.is_alphanumeric() && c != '#')
+            .map(|i| i + cursor)
+            .unwrap_or(line.len());
+
+        if start == end {
+            return None;
+        }
+
+        let range = self.encoding.line_range(pos.line, line, start..end);
+        let text = self.get_text(range);
+        info!(text, "Found word under cursor");
+
+        let kind = {
+            if Some(range) == self.result.ty {
+                ItemKind::Ty
+            } else if Some(range) == self.result.scope {
+                ItemKind::Scope
+            } else {
+                // TODO(texel, 2024-05-19): determine other types
+                return None;
+            }
+        };
+
+        Some(Item { kind, text, range })
+    }
+
+    /// Returns true if the commit message contains nothing but whitespace and git comments.
+    pub fn is_empty(&self) -> bool {
+        self.lines.iter().all(|l| is_blank(l) || is_comment(l))
+    }
+
+    /// All ticket references in the commit text, in order of appearance.
+    pub fn references(&self) -> impl Iterator<Item = &Reference> {
+        self.result.references.iter()
+    }
+
+    /// Every reference to a ticket or merge request in the commit text as [`Item`], ordered by
+    /// position. Unlike [`Self::lookup`] this does not depend on the cursor position.
+    pub fn all_references(&self) -> Vec<Item> {
+        let tickets = self.references().map(|r| Item {
+            kind: ItemKind::Ref(r.clone()),
+            text: r.text.clone(),
+            range: r.range,
+        });
+        let merge_requests = self.result.merge_requests.iter().filter_map(|r| {
+            Some(Item {
+                kind: ItemKind::MergeRequest(r.id()?),
+                text: r.text.clone(),
+                range: r.range,
+            })
+        });
+
+        let mut items: Vec<_> = tickets.chain(merge_requests).collect();
+        items.sort_by_key(|item| item.range.start);
+        items
+    }
+
+    /// Edits replacing every occurrence of the ticket reference at `pos` with `new_name`.
+    ///
+    /// `new_name` is either a complete reference (`#34`) or just the new key (`34`).
+    /// Returns `Ok(None)` if there is no reference at `pos`, and an error if the new
+    /// reference would not be recognized as such.
+    pub fn rename_reference(
+        &self,
+        pos: Position,
+        new_name: &str,
+    ) -> Result<Option<Vec<TextEdit>>, String> {
+        let Some(Item {
+            kind: ItemKind::Ref(reference),
+            ..
+        }) = self.lookup(pos)
+        else {
+            return Ok(None);
+        };
+
+        let is_reference = |text: &str| {
+            let lines = [text.to_owned()];
+            let mut references = find_references(&lines, &self.reference_pattern, self.encoding);
+            add_repo_prefixes(&mut references, &lines, self.encoding);
+            matches!(references.as_slice(), [r] if r.text == text)
+        };
+        let new_text = if is_reference(new_name) {
+            new_name.to_owned()
+        } else {
+            let mut text = reference.text.clone();
+            let key_start = text.rfind(&reference.key).unwrap_or_default();
+            text.replace_range(key_start..key_start + reference.key.len(), new_name);
+            text
+        };
+        if !is_reference(&new_text) {
+            return Err(format!("'{new_text}' is not a valid ticket reference"));
+        }
+
+        let edits = self
+            .references()
+            .filter(|r| r.text == reference.text)
+            .map(|r| self.text_edit(r.range, &new_text))
+            .collect();
+        Ok(Some(edits))
+    }
+
+    /// Returns the IDs of all tickets referenced anywhere in the commit text.
+    pub fn referenced_tickets(&self) -> Vec<u64> {
+        self.references().filter_map(Reference::id).collect()
+    }
+
+    pub fn commit_type_info(&self) -> Option<CommitElementDefinition> {
+        let ty = self.get_text(self.result.ty?);
+        self.config.types.iter().find(|t| t.name == ty).cloned()
+    }
+
+    pub fn commit_scope_info(&self) -> Option<CommitElementDefinition> {
+        let ty = self.get_text(self.result.scope?);
+        self.config
+            .scopes
+            .iter()
+            .cloned()
+            .chain(config::Repository::builtin_scopes())
+            .find(|t| t.name == ty)
+    }
+
+    pub fn get_commit_types(&self) -> &[CommitElementDefinition] {
+        &self.config.types
+    }
+
+    pub fn get_commit_scopes(&self) -> &[CommitElementDefinition] {
+        &self.config.scopes
+    }
+
+    /// Returns the configured scopes, with the scopes containing any of the `changed_files`
+    /// first. The flag is true for scopes matching a changed file.
+    pub fn rank_commit_scopes(
+        &self,
+        changed_files: &[PathBuf],
+    ) -> Vec<(&CommitElementDefinition, bool)> {
+        let mut scopes: Vec<_> = self
+            .config
+            .scopes
+            .iter()
+            .map(|scope| {
+                let matches = scope
+                    .paths
+                    .iter()
+                    .any(|path| changed_files.iter().any(|f| f.starts_with(path)));
+                (scope, matches)
+            })
+            .collect();
+
+        // stable sort keeps the configured order otherwise
+        scopes.sort_by_key(|(_, matches)| !matches);
+        scopes
+    }
+
+    /// A short description of the size of the message, e.g. `Subject 48/72 chars, body 3 lines`.
+    pub fn summary(&self) -> String {
+        let subject = self.lines.first().map_or(0, |s| s.graphemes(true).count());
+        let body = self.body_lines().count();
+        let body = if body == 1 {
+            "1 line".to_owned()
+        } else {
+            format!("{body} lines")
+        };
+
+        match self.config.subject_max_length.filter(|&l| l > 0) {
+            Some(limit) => format!("Subject {subject}/{limit} chars, body {body}"),
+            None => format!("Subject {subject} chars, body {body}"),
+        }
+    }
+
+    /// Indices of the non-blank lines between the subject and the trailers, ignoring comments.
+    fn body_lines(&self) -> impl DoubleEndedIterator<Item = usize> + '_ {
+        let trailers_start = self
+            .result
+            .trailers
+            .first()
+            .map_or(self.lines.len(), |t| t.key.start.line as usize);
+        (1..trailers_start).filter(|&idx| {
+            let line = &self.lines[idx];
+            !is_blank(line) && !is_comment(line)
+        })
+    }
+
+    /// An outline of the commit message: the header with its parts, the body and the trailers.
+    pub fn document_symbols(&self) -> Vec<DocumentSymbol> {
+        #[allow(deprecated)] // `deprecated` has to be set despite being deprecated
+        fn symbol(
+            name: String,
+            kind: SymbolKind,
+            range: Range,
+            children: Vec<DocumentSymbol>,
+        ) -> DocumentSymbol {
+            DocumentSymbol {
+                // clients reject empty names
+                name: if name.is_empty() {
+                    " ".to_owned()
+                } else {
+                    name
+                },
+                detail: None,
+                kind,
+                tags: None,
+                deprecated: None,
+                range,
+                selection_range: range,
+                children: (!children.is_empty()).then_some(children),
+            }
+        }
+
+        let Some(header) = self.lines.first() else {
+            return Vec::new();
+        };
+
+        let parts = [
+            (self.result.ty, SymbolKind::ENUM_MEMBER),
+            (self.result.scope, SymbolKind::NAMESPACE),
+            (self.result.subject, SymbolKind::STRING),
+        ];
+        let header_parts = parts
+            .into_iter()
+            .filter_map(|(range, kind)| {
+                let range = range?;
+                Some(symbol(self.get_text(range), kind, range, Vec::new()))
+            })
+            .collect();
+        let mut symbols = vec![symbol(
+            header.clone(),
+            SymbolKind::STRING,
+            self.full_line(0),
+            header_parts,
+        )];
+
+        let mut body = self.body_lines();
+        if let Some(first) = body.next() {
+            let last = body.next_back().unwrap_or(first);
+            let range = Range::new(
+                self.full_line(first as u32).start,
+                self.full_line(last as u32).end,
+            );
+            symbols.push(symbol(
+                "Body".to_owned(),
+                SymbolKind::MODULE,
+                range,
+                Vec::new(),
+            ));
+        }
+
+        symbols.extend(self.result.trailers.iter().map(|t| DocumentSymbol {
+            detail: Some(self.get_text(t.value)),
+            ..symbol(
+                self.get_text(t.key),
+                SymbolKind::PROPERTY,
+                Range::new(t.key.start, t.value.end),
+                Vec::new(),
+            )
+        }));
+
+        symbols
+    }
+
+    /// The highlighted elements of the commit message, ordered by position and without overlaps.
+    ///
+    /// Each range lies within a single line.
+    pub fn semantic_tokens(&self) -> Vec<(Range, SemanticTokenType)> {
+        let result = &self.result;
+        let mut tokens: Vec<_> = [
+            (result.ty, SemanticTokenType::KEYWORD),
+            (result.scope, SemanticTokenType::NAMESPACE),
+            (result.breaking, SemanticTokenType::OPERATOR),
+            (result.breaking_change_footer, SemanticTokenType::KEYWORD),
+        ]
+        .into_iter()
+        .filter_map(|(range, ty)| Some((range?, ty)))
+        .chain(
+            self.references()
+                .chain(&result.merge_requests)
+                .map(|r| (r.range, SemanticTokenType::NUMBER)),
+        )
+        .chain(
+            result
+                .trailers
+                .iter()
+                .map(|t| (t.key, SemanticTokenType::PROPERTY)),
+        )
+        .collect();
+
+        // the subject is split around the references it contains
+        if let Some(subject) = result.subject {
+            let mut start = subject.start;
+            let mut in_subject: Vec<_> = self
+                .references()
+                .chain(&result.merge_requests)
+                .filter(|r| r.range.start.line == 0)
+                .collect();
+            in_subject.sort_by_key(|r| r.range.start);
+            for reference in in_subject {
+                if start < reference.range.start {
+                    let range = Range::new(start, reference.range.start);
+                    tokens.push((range, SemanticTokenType::STRING));
+                }
+                start = start.max(reference.range.end);
+            }
+            if start < subject.end {
+                tokens.push((Range::new(start, subject.end), SemanticTokenType::STRING));
+            }
+        }
+
+        tokens.sort_by_key(|(range, _)| range.start);
+        tokens.dedup_by_key(|(range, _)| range.start);
+        tokens
+    }
+
+    /// The commit text with whitespace cleaned up.
+    ///
+    /// Trailing whitespace and trailing blank lines are removed and subject and body are separated
+    /// by exactly one blank line. Optionally the commit type is lowercased.
+    pub fn formatted_text(&self) -> String {
+        let mut lines: Vec<String> = self.lines.iter().map(|l| l.trim_end().to_owned()).collect();
+
+        if self.config.format_lowercase_type {
+            if let (Some(ty), Some(subject)) = (self.result.ty, lines.first_mut()) {
+                let start = self
+                    .encoding
+                    .byte_offset(subject, ty.start.character)
+                    .unwrap_or_default();
+                let end = self
+                    .encoding
+                    .byte_offset(subject, ty.end.character)
+                    .unwrap_or_default();
+                let lowercase = subject[start..end].to_lowercase();
+                subject.replace_range(start..end, &lowercase);
+            }
+        }
+
+        while lines.len() > 1 && lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+
+        if lines.len() > 1 {
+            let body_start = lines[1..]
+                .iter()
+                .position(|l| !l.is_empty())
+                .map_or(lines.len(), |idx| idx + 1);
+            lines.splice(1..body_start, [String::new()]);
+        }
+
+        let mut text = lines.join(self.line_ending.as_str());
+        if self.text.ends_with('\n') {
+            text.push_str(self.line_ending.as_str());
+        }
+        text
+    }
+
+    /// An edit replacing the whole document with the [formatted text](Self::formatted_text).
+    /// Returns `None` if the text is already formatted.
+    pub fn format(&self) -> Option<TextEdit> {
+        let formatted = self.formatted_text();
+        if formatted == self.text {
+            return None;
+        }
+
+        let line = self.text.matches('\n').count();
+        let last_line = self.text.rsplit('\n').next().unwrap_or_default();
+        let end = Position::new(line as u32, self.encoding.text_len(last_line) as u32);
+        Some(self.text_edit(Range::new(Position::new(0, 0), end), &formatted))
+    }
+
+    /// Edits fixing the problem reported by `diagnostic`, together with a description.
+    pub fn quick_fixes(&self, diagnostic: &lsp_types::Diagnostic) -> Vec<(String, Vec<TextEdit>)> {
+        let Some(lsp_types::NumberOrString::String(code)) = &diagnostic.code else {
+            return Vec::new();
+        };
+        let range = diagnostic.range;
+
+        match code.as_str() {
+            rule::TRAILING_PERIOD => vec![(
+                "Remove the trailing period".to_owned(),
+                vec![self.text_edit(range, "")],
+            )],
+            rule::UNKNOWN_TYPE => {
+                let ty = self.get_text(range).to_lowercase();
+                if !self.config.types.iter().any(|t| t.name == ty) {
+                    return Vec::new();
+                }
+                vec![(
+                    format!("Change type to '{ty}'"),
+                    vec![self.text_edit(range, &ty)],
+                )]
+            }
+            rule::MISSING_BLANK_LINE => vec![(
+                "Insert a blank line after the subject".to_owned(),
+                vec![self.text_edit(Range::new(range.start, range.start), "\n")],
+            )],
+            rule::BODY_LINE_TOO_LONG => {
+                let line = self.full_line(range.start.line);
+                let Some(limit) = self.config.body_max_line_length.filter(|&l| l > 0) else {
+                    return Vec::new();
+                };
+                let Some(wrapped) = wrap_line(&self.get_text(line), limit) else {
+                    return Vec::new();
+                };
+                vec![(
+                    "Wrap the line".to_owned(),
+                    vec![self.text_edit(line, &wrapped)],
+                )]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The line terminator used by the current document.
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Create an edit replacing `range` with `new_text`.
+    ///
+    /// All edits sent to the client must be created through this function, so that line breaks in
+    /// `new_text` are converted to the line ending of the document.
+    pub fn text_edit(&self, range: Range, new_text: &str) -> TextEdit {
+        TextEdit::new(range, self.line_ending.normalize(new_text))
+    }
+
+    /// Returns the range covering the line `idx`.
+    /// Lines past the end of the document yield an empty range.
+    fn full_line(&self, idx: u32) -> Range {
+        let len = self
+            .lines
+            .get(idx as usize)
+            .map_or(0, |l| self.encoding.text_len(l));
+        Range::new(Position::new(idx, 0), Position::new(idx, len as u32))
+    }
+
+    /// Returns the commit text inside the given range.
+    /// Returns an empty string if the range is not inside the text.
+    fn get_text(&self, range: Range) -> String {
+        // range.end.line is inclusive
+        let line_range = (range.start.line as usize)..=(range.end.line as usize);
+
+        let Some(lines) = self.lines.get(line_range) else {
+            return String::new();
+        };
+        let (Some(first), Some(last)) = (lines.first(), lines.last()) else {
+            return String::new();
+        };
+
+        // count bytes preceding the last line, taking newlines into account
+        let offset: usize = lines
+            .iter()
+            .take(lines.len() - 1) // do not count line containing "range.end"
+            .map(|l| l.len() + 1) // + 1 for the newlines we will add
+            .sum();
+
+        let (Some(start), Some(end)) = (
+            self.encoding.byte_offset(first, range.start.character),
+            self.encoding.byte_offset(last, range.end.character),
+        ) else {
+            return String::new();
+        };
+
+        let text = lines.join("\n");
+        text.get(start..end + offset).unwrap_or_default().to_owned()
+    }
+}
+
+/// The line terminator used inside a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// Determine the line ending of a text based on its first line break.
+    /// Texts without any line break use `\n`.
+    pub fn detect(text: &str) -> Self {
+        match text.find('\n') {
+            Some(idx) if text[..idx].ends_with('\r') => Self::CrLf,
+            _ => Self::Lf,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+
+    /// Convert all line breaks in `text` to this line ending.
+    pub fn normalize(self, text: &str) -> String {
+        let mut result = String::with_capacity(text.len());
+        for line in text.split_inclusive('\n') {
+            match line.strip_suffix('\n') {
+                Some(line) => {
+                    result.push_str(line.strip_suffix('\r').unwrap_or(line));
+                    result.push_str(self.as_str());
+                }
+                None => result.push_str(line),
+            }
+        }
+        result
+    }
+}
+
+/// The unit in which the `character` of a [`Position`] counts.
+///
+/// The language server protocol uses UTF-16 code units, unless the client supports another
+/// encoding. Everywhere else (like the output of `commit-lsp lint`) characters are counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PositionEncoding {
+    /// Unicode scalar values, i.e. characters
+    #[default]
+    Utf32,
+    Utf16,
+}
+
+impl PositionEncoding {
+    fn char_len(self, c: char) -> usize {
+        match self {
+            Self::Utf32 => 1,
+            Self::Utf16 => c.len_utf16(),
+        }
+    }
+
+    /// The length of `text` in units of this encoding.
+    pub fn text_len(self, text: &str) -> usize {
+        text.chars().map(|c| self.char_len(c)).sum()
+    }
+
+    /// Create a range inside of `line` from a range of byte offsets.
+    ///
+    /// Positions in a [`Range`] count units of the encoding, while string operations work with
+    /// bytes, so every range computed on the text must be converted through this function.
+    fn line_range(self, idx: u32, line: &str, bytes: std::ops::Range<usize>) -> Range {
+        partial_line(
+            idx,
+            self.offset(line, bytes.start)..self.offset(line, bytes.end),
+        )
+    }
+
+    /// Convert a byte offset in `line` to the number of units preceding it.
+    fn offset(self, line: &str, byte: usize) -> usize {
+        self.text_len(&line[..byte])
+    }
+
+    /// Convert an offset in units of the encoding in `line` to a byte offset.
+    /// Offsets inside of a character round up to the next character.
+    /// Returns `None` if the offset is past the end of the line.
+    fn byte_offset(self, line: &str, units: u32) -> Option<usize> {
+        let units = units as usize;
+        let mut count = 0;
+        for (idx, c) in line.char_indices() {
+            if count >= units {
+                return Some(idx);
+            }
+            count += self.char_len(c);
+        }
+        (count >= units).then_some(line.len())
+    }
+}
+
+impl From<PositionEncoding> for lsp_types::PositionEncodingKind {
+    fn from(value: PositionEncoding) -> Self {
+        match value {
+            PositionEncoding::Utf32 => Self::UTF32,
+            PositionEncoding::Utf16 => Self::UTF16,
+        }
+    }
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+/// Returns true for lines that git strips from the commit message.
+fn is_comment(line: &str) -> bool {
+    line.starts_with("# ") || line == "#"
+}
+
+fn partial_line(line: u32, range: std::ops::Range<usize>) -> Range {
+    Range::new(
+        Position::new(line, range.start as u32),
+        Position::new(line, range.end as u32),
+    )
+}
+
+/// Break `line` at whitespace into lines of at most `limit` characters, keeping its indentation
+/// on every line. Returns `None` if the line can not be wrapped, e.g. a single long url.
+fn wrap_line(line: &str, limit: usize) -> Option<String> {
+    let indent = &line[..line.len() - line.trim_start().len()];
+    let indent_length = indent.graphemes(true).count();
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut length = 0;
+    for word in line.split_whitespace() {
+        let word_length = word.graphemes(true).count();
+        if length > 0 && indent_length + length + 1 + word_length > limit {
+            lines.push(format!("{indent}{current}"));
+            current.clear();
+            length = 0;
+        } else if length > 0 {
+            current.push(' ');
+            length += 1;
+        }
+        current.push_str(word);
+        length += word_length;
+    }
+    lines.push(format!("{indent}{current}"));
+
+    (lines.len() > 1).then(|| lines.join("\n"))
+}
+
+/// Find the token of the `BREAKING CHANGE: ` (or `BREAKING-CHANGE: `) footer.
+fn find_breaking_change_footer(lines: &[String]) -> Option<Range> {
+    let footer_format = regex!(r"^BREAKING[ -]CHANGE: ");
+
+    lines
+        .iter()
+        .enumerate()
+        .skip(1) // the header can't be a footer
+        .rev()
+        .find(|(_, line)| footer_format.is_match(line))
+        .map(|(idx, _)| partial_line(idx as u32, 0.."BREAKING CHANGE".len()))
+}
+
+/// Parse the trailer block at the end of the message.
+///
+/// Like git, only the last paragraph is considered and only if all of its lines are trailers or
+/// continuation lines (starting with whitespace). Git comments are ignored.
+fn find_trailers(lines: &[String], encoding: PositionEncoding) -> Vec<Trailer> {
+    let trailer_format =
+        regex!(r"^(?P<key>[A-Za-z0-9][A-Za-z0-9-]*|BREAKING CHANGE): (?P<value>.*)$");
+
+    let mut paragraph: Vec<_> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| !is_comment(l))
+        .rev()
+        .skip_while(|(_, l)| is_blank(l))
+        .take_while(|(_, l)| !is_blank(l))
+        .collect();
+    paragraph.reverse();
+
+    // the trailers must be separated from the header by at least one blank line
+    if paragraph.first().is_none_or(|(idx, _)| *idx < 2) {
+        return Vec::new();
+    }
+
+    let mut trailers: Vec<Trailer> = Vec::new();
+    for (idx, line) in paragraph {
+        let idx = idx as u32;
+        if let Some(caps) = trailer_format.captures(line) {
+            let key = caps.name("key").expect("key is not optional").range();
+            let value = caps.name("value").expect("value is not optional").range();
+            trailers.push(Trailer {
+                key: encoding.line_range(idx, line, key),
+                value: encoding.line_range(idx, line, value),
+            });
+        } else if line.starts_with(char::is_whitespace) && !trailers.is_empty() {
+            let last = trailers.last_mut().expect("checked to be non empty");
+            last.value.end = Position::new(idx, encoding.text_len(line) as u32);
+        } else {
+            return Vec::new();
+        }
+    }
+
+    trailers
+}
+
+/// The configured pattern for references, falling back to the default if it is invalid.
+fn reference_pattern(config: &config::Repository) -> Regex {
+    match config
+        .issue_reference_pattern
+        .as_deref()
+        .map(compile_user_pattern)
+    {
+        Some(Ok(pattern)) => pattern,
+        Some(Err(e)) => {
+            warn!("Invalid issue reference pattern, using default: {e}");
+            default_reference_pattern().clone()
+        }
+        None => default_reference_pattern().clone(),
+    }
+}
+
+/// Compile the configured reference pattern and return the keys it finds in `sample`.
+///
+/// Unlike the analysis, this does not fall back to the default pattern if the configured one is
+/// invalid.
+pub fn sample_reference_keys(
+    config: &config::Repository,
+    sample: &str,
+) -> Result<Vec<String>, regex::Error> {
+    let pattern = match &config.issue_reference_pattern {
+        Some(pattern) => compile_user_pattern(pattern)?,
+        None => default_reference_pattern().clone(),
+    };
+    let lines = [sample.to_owned()];
+    Ok(find_references(&lines, &pattern, PositionEncoding::Utf32)
+        .into_iter()
+        .map(|r| r.key)
+        .collect())
+}
+
+/// The pattern for references if none is configured, e.g. `#123`.
+pub fn default_reference_pattern() -> &'static Regex {
+    regex!(r"#([0-9]+)")
+}
+
+/// The pattern for merge request references like `!123`.
+/// The `!` must not follow a word, so that it differs from the breaking change marker.
+fn merge_request_pattern() -> &'static Regex {
+    regex!(r"\B!([0-9]+)\b")
+}
+
+/// Find all ticket references together with their location in the text.
+fn find_references(
+    lines: &[String],
+    pattern: &Regex,
+    encoding: PositionEncoding,
+) -> Vec<Reference> {
+    let mut references = Vec::new();
+
+    for (idx, line) in lines.iter().enumerate() {
+        for caps in pattern.captures_iter(line) {
+            let full = caps.get(0).expect("There is always a full match");
+            let key = caps.get(1).unwrap_or(full).as_str();
+            references.push(Reference {
+                text: full.as_str().to_owned(),
+                key: key.to_owned(),
+                range: encoding.line_range(idx as u32, line, full.range()),
+                closing: false,
+                repo: None,
+            });
+        }
+    }
+
+    references
+}
+
+/// Extend the references that directly follow an `owner/repo` prefix (like `octocat/Hello-World#42`)
+/// to include the prefix, and set their [`Reference::repo`].
+fn add_repo_prefixes(references: &mut [Reference], lines: &[String], encoding: PositionEncoding) {
+    // not part of a longer path, e.g. of a url
+    let prefix = regex!(r"(?:^|[^\w/.-])([A-Za-z0-9-]+/[\w.-]+)$");
+
+    for reference in references {
+        let idx = reference.range.start.line;
+        let line = &lines[idx as usize];
+        let (Some(start), Some(end)) = (
+            encoding.byte_offset(line, reference.range.start.character),
+            encoding.byte_offset(line, reference.range.end.character),
+        ) else {
+            continue;
+        };
+        let Some(repo) = prefix.captures(&line[..start]).and_then(|c| c.get(1)) else {
+            continue;
+        };
+
+        reference.repo = Some(repo.as_str().to_owned());
+        reference.text = line[repo.start()..end].to_owned();
+        reference.range = encoding.line_range(idx, line, repo.start()..end);
+    }
+}
+
+/// Mark the references directly following one of the closing `keywords`, like `Closes #1` or
+/// `fixes: #1`.
+fn mark_closing_references(
+    references: &mut [Reference],
+    lines: &[String],
+    keywords: &[String],
+    encoding: PositionEncoding,
+) {
+    for reference in references {
+        let line = &lines[reference.range.start.line as usize];
+        let Some(start) = encoding.byte_offset(line, reference.range.start.character) else {
+            continue;
+        };
+        let before = &line[..start];
+        let separated = before.trim_end().trim_end_matches(':');
+        // the keyword must be followed by a space or colon, so `Closes#1` is no keyword
+        if separated.len() == before.len() {
+            continue;
+        }
+        let word = separated
+            .rsplit(|c: char| !c.is_alphanumeric())
+            .next()
+            .unwrap_or_default();
+        reference.closing = keywords.iter().any(|k| k.eq_ignore_ascii_case(word));
+    }
+}
+
+fn parse_header(first_line: &str) -> Option<(&str, Option<&str>, bool, &str)> {
+    let header_format = regex!(
+        r#"^(?P<ty>[a-zA-Z][a-zA-Z0-9-]*)(?:\((?P<scope>[^)]+)\))?(?P<breaking>!)?: (?P<subject>.*)$"#
+    );
+
+    let captures = header_format.captures(first_line)?;
+
+    let ty = captures.name("ty")?.as_str();
+    let scope = captures.name("scope").map(|m| m.as_str());
+    let breaking = captures.name("breaking").is_some();
+    let subject = captures.name("subject")?.as_str();
+
+    Some((ty, scope, breaking, subject))
+}
+
+/// Trailers (in lowercase) whose value names a person, `Signed-off-by` has to stay first.
+const IDENTITY_TRAILERS: &[&str] = &[
+    "signed-off-by",
+    "co-authored-by",
+    "reviewed-by",
+    "acked-by",
+    "tested-by",
+    "reported-by",
+    "suggested-by",
+    "helped-by",
+];
+
+/// Split a leading gitmoji (`✨` or `:sparkles:`) and the whitespace following it off the header.
+///
+/// Returns the byte range of the gitmoji and the rest of the header.
+fn split_gitmoji(header: &str) -> Option<(std::ops::Range<usize>, &str)> {
+    let gitmoji = regex!(
+        r"^(?P<gitmoji>:[a-z0-9_+-]+:|\p{Extended_Pictographic}[\p{Extended_Pictographic}\p{Emoji_Modifier}\x{FE0F}\x{200D}]*)\s*"
+    );
+    let captures = gitmoji.captures(header)?;
+    let end = captures.get(0)?.end();
+    Some((captures.name("gitmoji")?.range(), &header[end..]))
+}
+
+/// Explain what is wrong with a header that almost has the conventional format, e.g. `feat:thing`.
+///
+/// Returns the byte range of the problem and a description.
+fn diagnose_header(first_line: &str) -> Option<(std::ops::Range<usize>, &'static str)> {
+    let almost =
+        regex!(r#"^[a-zA-Z][a-zA-Z0-9-]*(?:\([^)]*\))?!?(?P<before>\s*):(?P<after>\s*)\S"#);
+    let captures = almost.captures(first_line)?;
+    let before = captures.name("before")?;
+    let after = captures.name("after")?;
+
+    if !before.is_empty() {
+        Some((before.range(), "There should be no space before the colon"))
+    } else if after.is_empty() {
+        let colon = before.end();
+        Some((colon..colon + 1, "The colon should be followed by a space"))
+    } else if after.as_str() != " " {
+        Some((
+            after.range(),
+            "The colon should be followed by a single space",
+        ))
+    } else {
+        None
+    }
+}
+
+/// Guess whether a word is a verb in past tense or progressive form.
+fn is_non_imperative(word: &str) -> bool {
+    // imperative verbs that happen to end in one of the suffixes
+    const EXCEPTIONS: &[&str] = &[
+        "bring", "embed", "exceed", "feed", "need", "proceed", "seed", "shed", "speed", "string",
+        "succeed",
+    ];
+
+    let word = word.to_lowercase();
+    if EXCEPTIONS.contains(&word.as_str()) {
+        return false;
+    }
+
+    // require a stem of at least two characters to skip words like "red" or "sing"
+    word.strip_suffix("ed").is_some_and(|stem| stem.len() > 1)
+        || word.strip_suffix("ing").is_some_and(|stem| stem.len() > 1)
+}
+
+/// Returns the token of a footer line like `Refs: #42` or `Closes #42`.
+fn footer_token(line: &str) -> Option<&str> {
+    let footer_format = regex!(r"^(?P<token>[A-Za-z][A-Za-z-]*)(?:: | #)");
+
+    Some(footer_format.captures(line)?.name("token")?.as_str())
+}
+
+/// Returns the offset of a string slice in another string slice.
+/// The second slice **MUST** point into part of the first.
+fn substr_offset<'needle, 'haystack: 'needle>(
+    container: &'haystack str,
+    contained: &'needle str,
+) -> std::ops::Range<usize> {
+    let delta = unsafe { contained.as_ptr().offset_from(container.as_ptr()) };
+    assert!(delta >= 0);
+
+    let delta = delta as usize;
+    assert!(delta + contained.len() <= container.len());
+
+    delta..delta + contained.len()
+}
+
+/// Stable identifiers of the lint rules, used as diagnostic codes.
+pub mod rule {
+    /// The header does not have the format `type(scope): subject`.
+    pub const INVALID_HEADER: &str = "invalid-header";
+    pub const UNKNOWN_TYPE: &str = "unknown-type";
+    pub const UNKNOWN_SCOPE: &str = "unknown-scope";
+    pub const SCOPE_NOT_STAGED: &str = "scope-not-staged";
+    pub const TRAILING_PERIOD: &str = "subject-trailing-period";
+    pub const IMPERATIVE_MOOD: &str = "imperative-mood";
+    pub const BREAKING_CHANGE: &str = "breaking-change";
+    pub const SIGN_OFF: &str = "sign-off";
+    pub const NON_ASCII_SUBJECT: &str = "non-ascii-subject";
+    pub const REFERENCE_KEYWORD: &str = "reference-keyword";
+    pub const MISSING_BLANK_LINE: &str = "empty-second-line";
+    pub const EMPTY_BODY: &str = "empty-body";
+    pub const EMPTY_SUBJECT: &str = "empty-subject";
+    pub const MISSING_BODY: &str = "missing-body";
+    pub const MISSING_SCOPE: &str = "missing-scope";
+    pub const DUPLICATE_TRAILER: &str = "duplicate-trailer";
+    pub const TRAILER_IDENTITY: &str = "trailer-identity";
+    pub const SUBJECT_TOO_LONG: &str = "subject-too-long";
+    pub const BODY_LINE_TOO_LONG: &str = "body-line-too-long";
+    pub const MISSING_TICKET: &str = "missing-ticket";
+}
+
+pub struct Diagnostic {
+    inner: lsp_types::Diagnostic,
+}
+
+impl Diagnostic {
+    pub fn new(range: Range, message: impl ToString) -> Self {
+        Self {
+            inner: lsp_types::Diagnostic {
+                range,
+                severity: None,
+                code: None,
+                code_description: None,
+                source: Some("commit-lsp".to_owned()),
+                message: message.to_string(),
+                related_information: None,
+                tags: None,
+                data: None,
+            },
+        }
+    }
+
+    /// Identify the rule that produced this diagnostic, see [`rule`].
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.inner.code = Some(lsp_types::NumberOrString::String(code.to_owned()));
+        self
+    }
+
+    pub fn with_severity(mut self, severity: DiagnosticSeverity) -> Self {
+        self.inner.severity = Some(severity);
+        self
+    }
+
+    /// The severity of this diagnostic. Diagnostics without explicit severity are errors.
+    pub fn severity(&self) -> DiagnosticSeverity {
+        self.inner.severity.unwrap_or(DiagnosticSeverity::ERROR)
+    }
+
+    /// Returns true if this diagnostic is at least as severe as the threshold.
+    pub fn fails(&self, threshold: FailureThreshold) -> bool {
+        let threshold = match threshold {
+            FailureThreshold::Error => DiagnosticSeverity::ERROR,
+            FailureThreshold::Warning => DiagnosticSeverity::WARNING,
+        };
+
+        // more severe diagnostics have lower values
+        self.severity() <= threshold
+    }
+}
+
+impl From<Severity> for DiagnosticSeverity {
+    fn from(value: Severity) -> Self {
+        match value {
+            Severity::Error => DiagnosticSeverity::ERROR,
+            Severity::Warning => DiagnosticSeverity::WARNING,
+            Severity::Info => DiagnosticSeverity::INFORMATION,
+            Severity::Hint => DiagnosticSeverity::HINT,
+        }
+    }
+}
+
+/// Machine readable form of a [`Diagnostic`]. Lines and columns start at 1.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct DiagnosticReport {
+    pub line: u32,
+    pub column: u32,
+    pub end_line: u32,
+    pub end_column: u32,
+    pub severity: &'static str,
+    pub code: Option<String>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn report(&self) -> DiagnosticReport {
+        let Range { start, end } = self.inner.range;
+        let severity = match self.severity() {
+            DiagnosticSeverity::ERROR => "error",
+            DiagnosticSeverity::WARNING => "warning",
+            DiagnosticSeverity::INFORMATION => "information",
+            _ => "hint",
+        };
+        let code = match &self.inner.code {
+            Some(lsp_types::NumberOrString::String(code)) => Some(code.clone()),
+            Some(lsp_types::NumberOrString::Number(code)) => Some(code.to_string()),
+            None => None,
+        };
+
+        DiagnosticReport {
+            line: start.line + 1,
+            column: start.character + 1,
+            end_line: end.line + 1,
+            end_column: end.character + 1,
+            severity,
+            code,
+            message: self.inner.message.clone(),
+        }
+    }
+}
+
+impl From<Diagnostic> for lsp_types::Diagnostic {
+    fn from(value: Diagnostic) -> Self {
+        value.inner
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let line = self.inner.range.start.line + 1;
+        let col = self.inner.range.start.character + 1;
+        let msg = &self.inner.message;
+        write!(f, "[{line}:{col}] {msg}")?;
+        if let Some(lsp_types::NumberOrString::String(code)) = &self.inner.code {
+            write!(f, " [{code}]")?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Item {
+    pub kind: ItemKind,
+    pub text: String,
+    pub range: Range,
+}
+
+/// The parts of the header line, see [`State::header_part`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderPart {
+    Type,
+    Scope,
+    /// Anything after the scope, including a `!` marker.
+    Subject,
+}
+
+/// An item of interest in the commit text.
+pub enum ItemKind {
+    /// The commit type (e.g. `feat`, `fix`)
+    Ty,
+    /// The commit scope
+    Scope,
+    /// A reference to a ticket/issue/etc
+    Ref(Reference),
+    /// A reference to a merge request with the given ID
+    MergeRequest(u64),
+    /// The token of a `BREAKING CHANGE: ` footer
+    BreakingChange,
+    /// The `!` marking a breaking change in the header
+    Breaking,
+    /// The key of a git trailer (e.g. `Signed-off-by`)
+    Trailer,
+    /// The subject of the commit reverted by a `Revert "..."` commit
+    Revert,
+    /// The emoji or `:shortcode:` in front of the type of a gitmoji header
+    Gitmoji,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Parse a text example with embedded range markers.
+    ///
+    /// `|>` will mark the start of the range and `<|` marks the end.
+    ///
+    /// Example input string:
+    /// ```gitcommit
+    /// The range |>is this part<| of the text.
+    /// ```
+    fn text_with_range(text: &str) -> (String, Range) {
+        let mut iter = text
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| l.contains("|>") || l.contains("<|"));
+
+        let (mut idx, mut line) = iter.next().unwrap();
+        let begin = {
+            let char = PositionEncoding::Utf32.offset(line, line.find("|>").unwrap());
+            Position::new(idx as u32, char as u32)
+        };
+
+        let end = {
+            let single_line = line.contains("<|");
+            if !single_line {
+                (idx, line) = iter.next().unwrap();
+            }
+
+            let mut char = PositionEncoding::Utf32.offset(line, line.find("<|").unwrap());
+
+            if single_line {
+                // skip the `|>` that precedes us in single line mode
+                char -= 2;
+            }
+            Position::new(idx as u32, char as u32)
+        };
+
+        assert_eq!(iter.next(), None);
+
+        (
+            text.replace("|>", "").replace("<|", ""),
+            Range::new(begin, end),
+        )
+    }
+
+    fn example(text: &str) -> (State, Range) {
+        let (text, range) = text_with_range(text);
+        let mut state = State::new(Default::default());
+        state.update_text(&text);
+        (state, range)
+    }
+
+    #[test]
+    fn test_get_text_single_line() {
+        let (state, range) = example("this |>is a<| test");
+
+        assert_eq!(state.get_text(range), "is a");
+    }
+
+    #[test]
+    fn test_get_text_full_single_line() {
+        let (state, range) = example("|>this is a test<|");
+
+        assert_eq!(state.get_text(range), "this is a test");
+    }
+
+    #[test]
+    fn test_get_text_full_empty_range() {
+        let (state, range) = example("this is|><| a test");
+
+        assert_eq!(state.get_text(range), "");
+    }
+
+    #[test]
+    fn test_get_text_multi_line() {
+        let (state, range) = example("this is a |>test\nover two<| lines");
+
+        assert_eq!(state.get_text(range), "test\nover two");
+    }
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(ToOwned::to_owned).collect()
+    }
+
+    #[test]
+    fn test_analysis_result() {
+        let (text, range) = text_with_range("feat(lsp)|>!<|: implement #42\n\nSee #7");
+        let result = AnalysisResult::parse(&lines(&text), default_reference_pattern());
+
+        assert_eq!(result.ty, Some(partial_line(0, 0..4)));
+        assert_eq!(result.scope, Some(partial_line(0, 5..8)));
+        assert_eq!(result.breaking, Some(range));
+        assert_eq!(result.subject, Some(partial_line(0, 12..25)));
+        assert_eq!(
+            result.references,
+            [
+                Reference {
+                    text: "#42".to_owned(),
+                    key: "42".to_owned(),
+                    range: partial_line(0, 22..25),
+                    closing: false,
+                    repo: None,
+                },
+                Reference {
+                    text: "#7".to_owned(),
+                    key: "7".to_owned(),
+                    range: partial_line(2, 4..6),
+                    closing: false,
+                    repo: None,
+                },
+            ]
+        );
+        assert!(result.errors.is_empty());
+    }
+
+    #[test]
+    fn test_cross_repo_references() {
+        let text = "fix: crash\n\nCloses octocat/Hello-World#42, #7\nhttps://example.com/a/b#1";
+        let result = AnalysisResult::parse(&lines(text), default_reference_pattern());
+
+        let references: Vec<_> = result
+            .references
+            .iter()
+            .map(|r| (r.text.as_str(), r.repo.as_deref(), r.id(), r.range))
+            .collect();
+        assert_eq!(
+            references,
+            [
+                (
+                    "octocat/Hello-World#42",
+                    Some("octocat/Hello-World"),
+                    None,
+                    partial_line(2, 7..29)
+                ),
+                ("#7", None, Some(7), partial_line(2, 31..33)),
+                ("#1", None, Some(1), partial_line(3, 23..25)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_reference_pattern() {
+        let mut state = State::new(config::Repository {
+            issue_reference_pattern: Some("[A-Z]+-[0-9]+".to_owned()),
+            ..Default::default()
+        });
+        state.update_text("feat: implement PROJ-123\n\nSee #7 and AB-1");
+
+        let keys: Vec<_> = state.references().map(|r| r.key.as_str()).collect();
+        assert_eq!(keys, ["PROJ-123", "AB-1"]);
+
+        let item = state.lookup(Position::new(0, 20)).unwrap();
+        let ItemKind::Ref(reference) = item.kind else {
+            panic!("Expected a reference");
+        };
+        assert_eq!(reference.key, "PROJ-123");
+        assert_eq!(reference.id(), None);
+        assert_eq!(item.text, "PROJ-123");
+    }
+
+    fn apply(state: &mut State, range: Range, text: &str) -> String {
+        state.apply_change(range, text);
+        state.text.clone()
+    }
+
+    #[test]
+    fn test_apply_change_insert_char() {
+        let mut state = State::new(Default::default());
+        state.update_text("fix: thing\n\nbody\n");
+
+        let text = apply(&mut state, partial_line(2, 4..4), "!");
+        assert_eq!(text, "fix: thing\n\nbody!\n");
+        assert_eq!(state.lines, ["fix: thing", "", "body!"]);
+    }
+
+    #[test]
+    fn test_apply_change_delete_lines() {
+        let mut state = State::new(Default::default());
+        state.update_text("fix: thing\r\n\r\nfirst\r\nsecond\r\nthird\r\n");
+
+        let range = Range::new(Position::new(2, 0), Position::new(4, 0));
+        let text = apply(&mut state, range, "");
+        assert_eq!(text, "fix: thing\r\n\r\nthird\r\n");
+    }
+
+    #[test]
+    fn test_apply_change_at_end() {
+        let mut state = State::new(Default::default());
+        state.update_text("fix: thing\n");
+
+        let text = apply(&mut state, partial_line(1, 0..0), "\nbody");
+        assert_eq!(text, "fix: thing\n\nbody");
+
+        // positions past the end are clamped
+        let text = apply(&mut state, partial_line(5, 10..10), "!");
+        assert_eq!(text, "fix: thing\n\nbody!");
+    }
+
+    #[test]
+    fn test_apply_change_reparses_header() {
+        let mut state = State::new(Default::default());
+        state.update_text("fix: thing");
+        assert_eq!(state.result.scope, None);
+
+        state.apply_change(partial_line(0, 3..3), "(lsp)");
+        assert_eq!(state.result.scope, Some(partial_line(0, 4..7)));
+
+        state.apply_change(partial_line(0, 0..3), "feat");
+        let item = state.lookup(Position::new(0, 1)).unwrap();
+        assert!(matches!(item.kind, ItemKind::Ty));
+        assert_eq!(item.text, "feat");
+    }
+
+    #[test]
+    fn test_header_part() {
+        fn part(state: &State, character: u32) -> Option<HeaderPart> {
+            state.header_part(Position::new(0, character))
+        }
+        let mut state = State::new(Default::default());
+        state.update_text("feat(lsp): subject");
+
+        assert_eq!(part(&state, 0), Some(HeaderPart::Type));
+        assert_eq!(part(&state, 4), Some(HeaderPart::Type));
+        assert_eq!(part(&state, 5), Some(HeaderPart::Scope));
+        assert_eq!(part(&state, 8), Some(HeaderPart::Scope));
+        assert_eq!(part(&state, 9), Some(HeaderPart::Subject));
+        assert_eq!(part(&state, 12), Some(HeaderPart::Subject));
+        assert_eq!(part(&state, 100), None);
+        assert_eq!(state.header_part(Position::new(1, 0)), None);
+
+        state.update_text("fix: (subject)");
+        assert_eq!(part(&state, 6), Some(HeaderPart::Subject));
+
+        state.update_text("");
+        assert_eq!(part(&state, 0), Some(HeaderPart::Type));
+    }
+
+    #[test]
+    fn test_lookup_after_emoji() {
+        let (state, range) = example("fix: 🎉 |>#42<| works");
+        assert_eq!(range, partial_line(0, 7..10));
+
+        let item = state.lookup(Position::new(0, 8)).unwrap();
+        assert!(matches!(item.kind, ItemKind::Ref(_)));
+        assert_eq!(item.text, "#42");
+        assert_eq!(item.range, range);
+    }
+
+    #[test]
+    fn test_non_ascii_scope() {
+        let (state, range) = example("feat(|>café<|): 🚀 launch");
+
+        assert_eq!(state.result.scope, Some(range));
+        assert_eq!(state.result.subject, Some(partial_line(0, 12..20)));
+        assert_eq!(state.get_text(range), "café");
+
+        let item = state.lookup(Position::new(0, 8)).unwrap();
+        assert!(matches!(item.kind, ItemKind::Scope));
+        assert_eq!(item.text, "café");
+    }
+
+    #[test]
+    fn test_references() {
+        let mut state = State::new(Default::default());
+        state.update_text("fix: crash #1\n\nSee #22, #1\n\nRefs: #333");
+
+        let references: Vec<_> = state
+            .references()
+            .map(|r| (r.text.as_str(), r.range))
+            .collect();
+        assert_eq!(
+            references,
+            [
+                ("#1", partial_line(0, 11..13)),
+                ("#22", partial_line(2, 4..7)),
+                ("#1", partial_line(2, 9..11)),
+                ("#333", partial_line(4, 6..10)),
+            ]
+        );
+        assert_eq!(state.referenced_tickets(), [1, 22, 1, 333]);
+    }
+
+    fn closing_references(state: &State) -> Vec<(&str, bool)> {
+        state
+            .references()
+            .map(|r| (r.text.as_str(), r.closing))
+            .collect()
+    }
+
+    #[test]
+    fn test_closing_references() {
+        let mut state = State::new(Default::default());
+        state.update_text("fix: crash\n\nCloses #1\nSee #2\nfixes: #3, #4\nPrefixes #5\nCloses#6");
+
+        assert_eq!(
+            closing_references(&state),
+            [
+                ("#1", true),
+                ("#2", false),
+                ("#3", true),
+                ("#4", false),
+                ("#5", false),
+                ("#6", false),
+            ]
+        );
+
+        let item = state.lookup(Position::new(2, 8)).unwrap();
+        assert!(matches!(
+            item.kind,
+            ItemKind::Ref(Reference { closing: true, .. })
+        ));
+    }
+
+    #[test]
+    fn test_closing_keywords_configurable() {
+        let config = config::Repository {
+            closing_keywords: vec!["Implements".to_owned()],
+            ..Default::default()
+        };
+        let mut state = State::new(config);
+        state.update_text("feat: thing\n\nImplements #1\nCloses #2");
+
+        assert_eq!(closing_references(&state), [("#1", true), ("#2", false)]);
+    }
+
+    #[test]
+    fn test_lookup_cross_repo_reference() {
+        let mut state = State::new(Default::default());
+        state.update_text("fix: crash\n\nCloses octocat/Hello-World#42");
+
+        let item = state.lookup(Position::new(2, 10)).unwrap();
+        let ItemKind::Ref(reference) = item.kind else {
+            panic!("Expected a reference");
+        };
+        assert_eq!(reference.repo.as_deref(), Some("octocat/Hello-World"));
+        assert_eq!(reference.key, "42");
+        assert!(reference.closing);
+        assert_eq!(state.referenced_tickets(), Vec::<u64>::new());
+
+        let edits = state.rename_reference(Position::new(2, 10), "43").unwrap();
+        assert_eq!(edits.unwrap()[0].new_text, "octocat/Hello-World#43");
+    }
+
+    fn reference_texts(state: &State) -> Vec<(String, Range)> {
+        state
+            .all_references()
+            .into_iter()
+            .map(|item| (item.text, item.range))
+            .collect()
+    }
+
+    #[test]
+    fn test_all_references_on_one_line() {
+        let mut state = State::new(Default::default());
+        state.update_text("fix: crash\n\nFixes #12, closes #34");
+
+        assert_eq!(
+            reference_texts(&state),
+            [
+                ("#12".to_owned(), partial_line(2, 6..9)),
+                ("#34".to_owned(), partial_line(2, 18..21)),
+            ]
+        );
+        assert!(state
+            .all_references()
+            .iter()
+            .all(|item| matches!(item.kind, ItemKind::Ref(_))));
+    }
+
+    #[test]
+    fn test_all_references_across_lines() {
+        let mut state = State::new(config::Repository {
+            merge_request_references: true,
+            ..Default::default()
+        });
+        state.update_text("fix: crash #1\n\nSee !7 and #22\nalso #3\n");
+
+        assert_eq!(
+            reference_texts(&state),
+            [
+                ("#1".to_owned(), partial_line(0, 11..13)),
+                ("!7".to_owned(), partial_line(2, 4..6)),
+                ("#22".to_owned(), partial_line(2, 11..14)),
+                ("#3".to_owned(), partial_line(3, 5..7)),
+            ]
+        );
+        assert!(matches!(
+            state.all_references()[1].kind,
+            ItemKind::MergeRequest(7)
+        ));
+    }
+
+    #[test]
+    fn test_check_references_exist() {
+        let mut state = State::new(config::Repository {
+            check_references_exist: true,
+            ..Default::default()
+        });
+        state.update_text("fix: crash\n\nCloses #12, #9999");
+
+        let diagnostics = state.check_references_exist(|id| id == 9999);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(state.get_text(diagnostics[0].inner.range), "#9999");
+        assert_eq!(diagnostics[0].inner.message, "Ticket #9999 does not exist");
+
+        state.set_config(Default::default());
+        assert!(state.check_references_exist(|_| true).is_empty());
+    }
+
+    #[test]
+    fn test_ticket_query() {
+        let mut state = State::new(Default::default());
+        state.update_text("fix: crash\n\nCloses #12 and #log");
+
+        assert_eq!(
+            state.ticket_query(Position::new(2, 10)).as_deref(),
+            Some("12")
+        );
+        assert_eq!(state.ticket_query(Position::new(2, 8)).as_deref(), Some(""));
+        assert_eq!(
+            state.ticket_query(Position::new(2, 19)).as_deref(),
+            Some("log")
+        );
+        assert_eq!(state.ticket_query(Position::new(2, 12)), None);
+        assert_eq!(state.ticket_query(Position::new(0, 3)), None);
+    }
+
+    #[test]
+    fn test_default_reference_pattern() {
+        let (state, range) = example("feat: implement |>#123<|");
+
+        let item = state.lookup(Position::new(0, 18)).unwrap();
+        let ItemKind::Ref(reference) = item.kind else {
+            panic!("Expected a reference");
+        };
+        assert_eq!(reference.text, "#123");
+        assert_eq!(reference.id(), Some(123));
+        assert_eq!(item.range, range);
+    }
+
+    #[test]
+    fn test_invalid_reference_pattern_uses_default() {
+        let mut state = State::new(config::Repository {
+            issue_reference_pattern: Some("[A-Z".to_owned()),
+            ..Default::default()
+        });
+        state.update_text("feat: implement #123");
+
+        assert_eq!(state.referenced_tickets(), [123]);
+    }
+
+    #[test]
+    fn test_merge_request_references() {
+        let mut state = State::new(config::Repository {
+            merge_request_references: true,
+            ..Default::default()
+        });
+        state.update_text("feat(api)!: follow up to !12\n\nSee #3 and !45\n\nBREAKING CHANGE: x");
+
+        let merge_requests: Vec<_> = state
+            .result()
+            .merge_requests
+            .iter()
+            .map(|r| (r.text.as_str(), r.range))
+            .collect();
+        assert_eq!(
+            merge_requests,
+            [
+                ("!12", partial_line(0, 25..28)),
+                ("!45", partial_line(2, 11..14))
+            ]
+        );
+        assert_eq!(state.referenced_tickets(), [3]);
+        assert_eq!(state.result().breaking, Some(partial_line(0, 9..10)));
+
+        let item = state.lookup(Position::new(2, 12)).unwrap();
+        assert!(matches!(item.kind, ItemKind::MergeRequest(45)));
+
+        state.set_config(Default::default());
+        assert!(state.result().merge_requests.is_empty());
+    }
+
+    #[test]
+    fn test_analysis_result_invalid_header() {
+        let result =
+            AnalysisResult::parse(&lines("implement the thing"), default_reference_pattern());
+
+        assert_eq!(result.ty, None);
+        assert_eq!(result.subject, None);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].range, partial_line(0, 0..19));
+    }
+
+    #[test]
+    fn test_analysis_result_empty() {
+        assert_eq!(
+            AnalysisResult::parse(&[], default_reference_pattern()),
+            AnalysisResult::default()
+        );
+    }
+
+    #[test]
+    fn test_empty_document() {
+        let mut state = State::new(Default::default());
+        state.update_text("");
+
+        assert!(state.is_empty());
+        assert!(state.all_diagnostics().is_empty());
+        assert!(state.lookup(Position::new(0, 0)).is_none());
+        assert_eq!(state.full_line(0), Range::default());
+        assert_eq!(state.get_text(Range::default()), "");
+    }
+
+    #[test]
+    fn test_out_of_range_access() {
+        let (state, _) = example("|>feat: thing<|");
+
+        assert!(state.lookup(Position::new(0, 100)).is_none());
+        assert!(state.lookup(Position::new(3, 0)).is_none());
+        assert_eq!(state.get_text(state.full_line(3)), "");
+        assert_eq!(
+            state.get_text(Range::new(Position::new(0, 6), Position::new(0, 100))),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_lookup_inside_multibyte_character() {
+        let (state, _) = example("|>feat: naïve<|");
+
+        assert!(state.lookup(Position::new(0, 9)).is_none());
+    }
+
+    #[test]
+    fn test_breaking_change_footer() {
+        let (text, range) =
+            text_with_range("feat!: thing\n\nbody\n\n|>BREAKING CHANGE<|: config moved");
+        let result = AnalysisResult::parse(&lines(&text), default_reference_pattern());
+
+        assert_eq!(result.breaking_change_footer, Some(range));
+    }
+
+    #[test]
+    fn test_breaking_change_footer_with_dash() {
+        let (text, range) = text_with_range("feat: thing\n\n|>BREAKING-CHANGE<|: config moved");
+        let result = AnalysisResult::parse(&lines(&text), default_reference_pattern());
+
+        assert_eq!(result.breaking_change_footer, Some(range));
+    }
+
+    #[test]
+    fn test_breaking_change_footer_is_case_sensitive() {
+        let result = AnalysisResult::parse(
+            &lines("feat: thing\n\nbreaking change: config moved"),
+            default_reference_pattern(),
+        );
+
+        assert_eq!(result.breaking_change_footer, None);
+    }
+
+    fn trailer_texts(text: &str) -> Vec<(String, String)> {
+        let mut state = State::new(Default::default());
+        state.update_text(text);
+        state
+            .result
+            .trailers
+            .iter()
+            .map(|t| (state.get_text(t.key), state.get_text(t.value)))
+            .collect()
+    }
+
+    #[test]
+    fn test_trailers() {
+        let trailers = trailer_texts(
+            "feat: thing\n\nbody\n\nReviewed-by: A <a@example.com>\nSigned-off-by: B <b@example.com>\n",
+        );
+
+        assert_eq!(
+            trailers,
+            [
+                ("Reviewed-by".to_owned(), "A <a@example.com>".to_owned()),
+                ("Signed-off-by".to_owned(), "B <b@example.com>".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trailers_only_in_last_paragraph() {
+        let trailers = trailer_texts(
+            "feat: thing\n\nNote: this looks like a trailer\n\nbody text\nSigned-off-by: B <b@example.com>",
+        );
+        assert!(trailers.is_empty());
+
+        let trailers = trailer_texts(
+            "feat: thing\n\nNote: this looks like a trailer\nbut is body text\n\nAcked-by: C\n\n# comment",
+        );
+        assert_eq!(trailers, [("Acked-by".to_owned(), "C".to_owned())]);
+    }
+
+    #[test]
+    fn test_trailer_continuation_line() {
+        let trailers = trailer_texts("feat: thing\n\nBREAKING CHANGE: config\n  moved\nRefs: #42");
+
+        assert_eq!(
+            trailers,
+            [
+                ("BREAKING CHANGE".to_owned(), "config\n  moved".to_owned()),
+                ("Refs".to_owned(), "#42".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_header_is_not_a_trailer() {
+        assert!(trailer_texts("Note: thing\nSigned-off-by: B").is_empty());
+    }
+
+    #[test]
+    fn test_lookup_trailer() {
+        let (state, range) = example("feat: thing\n\n|>Co-authored-by<|: A <a@example.com>");
+
+        let item = state.lookup(Position::new(2, 3)).unwrap();
+        assert!(matches!(item.kind, ItemKind::Trailer));
+        assert_eq!(item.range, range);
+    }
+
+    fn trailer_diagnostics(require_sign_off: bool, text: &str) -> Vec<(String, String)> {
+        let mut state = State::new(config::Repository {
+            require_sign_off,
+            ..Default::default()
+        });
+        state.update_text(text);
+        state
+            .check_trailers()
+            .into_iter()
+            .map(|d| (state.get_text(d.inner.range), d.report().code.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_duplicate_trailers() {
+        let text = "feat: thing\n\nbody\n\n\
+                    Co-authored-by: A <a@example.com>\n\
+                    Refs: #1\n\
+                    co-authored-by: A <a@example.com>\n\
+                    Co-authored-by: B <b@example.com>";
+        assert_eq!(
+            trailer_diagnostics(false, text),
+            [(
+                "co-authored-by: A <a@example.com>".to_owned(),
+                rule::DUPLICATE_TRAILER.to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_trailer_identity() {
+        let text = "feat: thing\n\nbody\n\n\
+                    Reviewed-by: A\n\
+                    Signed-off-by: B\n\
+                    Tested-by: C <c at example dot com>\n\
+                    Acked-by: <d@localhost>\n\
+                    Refs: #1";
+        let identity = |(text, _): &(String, String)| text.clone();
+        assert_eq!(
+            trailer_diagnostics(false, text)
+                .iter()
+                .map(identity)
+                .collect::<Vec<_>>(),
+            [
+                "Reviewed-by: A",
+                "Signed-off-by: B",
+                "Tested-by: C <c at example dot com>"
+            ]
+        );
+        // reported by `check_sign_off` instead
+        assert_eq!(
+            trailer_diagnostics(true, text)
+                .iter()
+                .map(identity)
+                .collect::<Vec<_>>(),
+            ["Reviewed-by: A", "Tested-by: C <c at example dot com>"]
+        );
+    }
+
+    fn sign_off_diagnostics(text: &str) -> Vec<String> {
+        let mut state = State::new(config::Repository {
+            require_sign_off: true,
+            ..Default::default()
+        });
+        state.update_text(text);
+        state
+            .check_sign_off()
+            .into_iter()
+            .map(|d| state.get_text(d.inner.range))
+            .collect()
+    }
+
+    #[test]
+    fn test_signed_off() {
+        assert!(sign_off_diagnostics(
+            "fix: thing\n\nbody\n\nSigned-off-by: Jane Doe <jane@example.com>"
+        )
+        .is_empty());
+        assert!(
+            sign_off_diagnostics("fix: thing\n\nsigned-off-by: Jane Doe <jane@example.com>")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_missing_sign_off() {
+        assert_eq!(
+            sign_off_diagnostics("fix: thing\n\nbody\n\n# Please enter the commit message"),
+            ["body"]
+        );
+    }
+
+    #[test]
+    fn test_malformed_sign_off() {
+        assert_eq!(
+            sign_off_diagnostics("fix: thing\n\nSigned-off-by: Jane Doe"),
+            ["Jane Doe"]
+        );
+        assert_eq!(
+            sign_off_diagnostics("fix: thing\n\nSigned-off-by: <jane@example.com>"),
+            ["<jane@example.com>"]
+        );
+    }
+
+    #[test]
+    fn test_sign_off_not_required_by_default() {
+        let (state, _) = example("|>fix: thing<|");
+
+        assert!(state.check_sign_off().is_empty());
+    }
+
+    fn breaking_change_diagnostic(check: BreakingChangeCheck, text: &str) -> Option<String> {
+        let mut state = State::new(config::Repository {
+            breaking_change_check: check,
+            ..Default::default()
+        });
+        state.update_text(text);
+        state
+            .check_breaking_change()
+            .map(|d| state.get_text(d.inner.range))
+    }
+
+    #[test]
+    fn test_breaking_change_requires_footer() {
+        let text = "feat!: thing";
+
+        assert_eq!(
+            breaking_change_diagnostic(BreakingChangeCheck::None, text),
+            None
+        );
+        assert_eq!(
+            breaking_change_diagnostic(BreakingChangeCheck::Footer, text),
+            Some("!".to_owned())
+        );
+        assert_eq!(
+            breaking_change_diagnostic(BreakingChangeCheck::Marker, text),
+            None
+        );
+        assert_eq!(
+            breaking_change_diagnostic(BreakingChangeCheck::Both, text),
+            Some("!".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_breaking_change_requires_marker() {
+        let text = "feat: thing\n\nBREAKING CHANGE: config moved";
+        let token = Some("BREAKING CHANGE".to_owned());
+
+        assert_eq!(
+            breaking_change_diagnostic(BreakingChangeCheck::None, text),
+            None
+        );
+        assert_eq!(
+            breaking_change_diagnostic(BreakingChangeCheck::Footer, text),
+            None
+        );
+        assert_eq!(
+            breaking_change_diagnostic(BreakingChangeCheck::Marker, text),
+            token
+        );
+        assert_eq!(
+            breaking_change_diagnostic(BreakingChangeCheck::Both, text),
+            token
+        );
+    }
+
+    #[test]
+    fn test_breaking_change_consistent() {
+        let text = "feat!: thing\n\nBREAKING CHANGE: config moved";
+
+        assert_eq!(
+            breaking_change_diagnostic(BreakingChangeCheck::Both, text),
+            None
+        );
+    }
+
+    #[test]
+    fn test_lookup_breaking_change_footer() {
+        let (state, range) = example("feat: thing\n\n|>BREAKING CHANGE<|: config moved");
+
+        let item = state.lookup(Position::new(2, 10)).unwrap();
+        assert!(matches!(item.kind, ItemKind::BreakingChange));
+        assert_eq!(item.range, range);
+    }
+
+    #[test]
+    fn test_parse_header_with_digits_and_hyphens() {
+        let (ty, scope, _, subject) = parse_header("feat2(ui-v2): thing").unwrap();
+        assert_eq!(ty, "feat2");
+        assert_eq!(scope, Some("ui-v2"));
+        assert_eq!(subject, "thing");
+
+        assert_eq!(parse_header("hot-fix: thing").unwrap().0, "hot-fix");
+        assert!(parse_header("2feat: thing").is_none());
+        assert!(parse_header("some words feat: thing").is_none());
+    }
+
+    fn gitmoji_state(text: &str) -> State {
+        let mut state = State::new(config::Repository {
+            header_style: HeaderStyle::Gitmoji,
+            ascii_only_subject: true,
+            ..Default::default()
+        });
+        state.update_text(text);
+        state
+    }
+
+    #[test]
+    fn test_rename_reference() {
+        let (state, range) = example("fix: crash in |>#12<|\n\nSee #123 and #12.\n\nRefs: #12");
+        let edits = state.rename_reference(range.start, "34").unwrap().unwrap();
+        let ranges: Vec<_> = edits.iter().map(|e| state.get_text(e.range)).collect();
+        assert_eq!(ranges, ["#12", "#12", "#12"]);
+        assert!(edits.iter().all(|e| e.new_text == "#34"));
+
+        let edits = state.rename_reference(range.start, "#56").unwrap().unwrap();
+        assert!(edits.iter().all(|e| e.new_text == "#56"));
+
+        assert!(state.rename_reference(range.start, "abc").is_err());
+        assert_eq!(state.rename_reference(Position::new(0, 1), "34"), Ok(None));
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut state = State::new(Default::default());
+        state.update_text("feat: thing\n\nfirst\n\nsecond\nthird\n# comment\n\nRefs: #1");
+        assert_eq!(state.summary(), "Subject 11/72 chars, body 3 lines");
+
+        state.set_config(config::Repository {
+            subject_max_length: Some(0),
+            ..Default::default()
+        });
+        state.update_text("feat: ä\n\nbody");
+        assert_eq!(state.summary(), "Subject 7 chars, body 1 line");
+    }
+
+    #[test]
+    fn test_lookup_breaking_marker() {
+        let (state, range) = example("feat(api)|>!<|: remove endpoint");
+
+        let item = state.lookup(range.start).unwrap();
+        assert!(matches!(item.kind, ItemKind::Breaking));
+        assert_eq!(item.range, range);
+        assert_eq!(item.text, "!");
+
+        assert!(state.lookup(range.end).is_none());
+    }
+
+    #[test]
+    fn test_gitmoji_emoji() {
+        let state = gitmoji_state("✨ feat(ui): add thing");
+        let result = state.result();
+        assert_eq!(state.get_text(result.gitmoji.unwrap()), "✨");
+        assert_eq!(state.get_text(result.ty.unwrap()), "feat");
+        assert_eq!(state.get_text(result.scope.unwrap()), "ui");
+        assert_eq!(state.get_text(result.subject.unwrap()), "add thing");
+        assert!(state.all_diagnostics().is_empty());
+
+        let state = gitmoji_state("⚡️fix: faster");
+        assert_eq!(state.get_text(state.result().gitmoji.unwrap()), "⚡️");
+        assert_eq!(state.get_text(state.result().ty.unwrap()), "fix");
+
+        let item = state.lookup(Position::new(0, 0)).unwrap();
+        assert!(matches!(item.kind, ItemKind::Gitmoji));
+        assert_eq!(item.text, "⚡️");
+    }
+
+    #[test]
+    fn test_gitmoji_shortcode() {
+        let state = gitmoji_state(":sparkles: feat!: add thing");
+        let result = state.result();
+        assert_eq!(state.get_text(result.gitmoji.unwrap()), ":sparkles:");
+        assert_eq!(state.get_text(result.ty.unwrap()), "feat");
+        assert!(result.breaking.is_some());
+        assert_eq!(state.get_text(result.subject.unwrap()), "add thing");
+        assert_eq!(
+            state.header_part(Position::new(0, 12)),
+            Some(HeaderPart::Type)
+        );
+        assert_eq!(state.header_part(Position::new(0, 3)), None);
+    }
+
+    #[test]
+    fn test_gitmoji_is_optional() {
+        let state = gitmoji_state("feat: add thing");
+        assert!(state.result().gitmoji.is_none());
+        assert_eq!(state.get_text(state.result().ty.unwrap()), "feat");
+
+        // without gitmoji headers, the emoji makes the header invalid
+        let (state, _) = example("|>✨ feat: add thing<|");
+        assert!(state.result().gitmoji.is_none());
+        assert!(state.result().ty.is_none());
+    }
+
+    #[test]
+    fn test_malformed_header() {
+        let errors = |header: &str| -> Vec<_> {
+            AnalysisResult::parse(&[header.to_owned()], default_reference_pattern())
+                .errors
+                .into_iter()
+                .map(|e| (e.range.start.character..e.range.end.character, e.message))
+                .collect()
+        };
+
+        assert_eq!(errors("feat2: thing"), []);
+        assert_eq!(
+            errors("feat : thing"),
+            [(4..5, "There should be no space before the colon".to_owned())]
+        );
+        assert_eq!(
+            errors("feat:no-space"),
+            [(4..5, "The colon should be followed by a space".to_owned())]
+        );
+        assert_eq!(
+            errors("feat(ui):  thing"),
+            [(
+                9..11,
+                "The colon should be followed by a single space".to_owned()
+            )]
+        );
+        assert_eq!(
+            errors("Add a thing"),
+            [(
+                0..11,
+                "The header should have the format `type(scope): subject`".to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_parse_header_with_scope() {
+        let example = "feat(lsp): implement the thing";
+
+        let (ty, scope, breaking, subject) = parse_header(example).unwrap();
+
+        assert_eq!(ty, "feat");
+        assert_eq!(scope, Some("lsp"));
+        assert!(!breaking);
+        assert_eq!(subject, "implement the thing");
+    }
+
+    #[test]
+    fn test_parse_header_without_scope() {
+        let example = "feat: implement the thing";
+
+        let (ty, scope, breaking, subject) = parse_header(example).unwrap();
+
+        assert_eq!(ty, "feat");
+        assert_eq!(scope, None);
+        assert!(!breaking);
+        assert_eq!(subject, "implement the thing");
+    }
+
+    #[test]
+    fn test_parse_header_with_dependency_scope() {
+        let example = "chore(deps-dev): bump wiremock from 0.6.3 to 0.6.4";
+
+        let (ty, scope, _, subject) = parse_header(example).unwrap();
+
+        assert_eq!(ty, "chore");
+        assert_eq!(scope, Some("deps-dev"));
+        assert_eq!(subject, "bump wiremock from 0.6.3 to 0.6.4");
+    }
+
+    #[test]
+    fn test_parse_header_with_scope_and_breaking_change() {
+        let example = "feat(lsp)!: implement the thing";
+
+        let (ty, scope, breaking, subject) = parse_header(example).unwrap();
+
+        assert_eq!(ty, "feat");
+        assert_eq!(scope, Some("lsp"));
+        assert!(breaking);
+        assert_eq!(subject, "implement the thing");
+    }
+
+    #[test]
+    fn test_subject_within_limit() {
+        let (state, _) = example("|>feat: a short subject<|");
+
+        assert!(state.check_subject_length().is_none());
+    }
+
+    #[test]
+    fn test_subject_too_long() {
+        let mut state = State::new(config::Repository {
+            subject_max_length: Some(10),
+            ..Default::default()
+        });
+        state.update_text("feat: too long subject");
+
+        let diag = state.check_subject_length().unwrap();
+        assert_eq!(state.get_text(diag.inner.range), "long subject");
+    }
+
+    #[test]
+    fn test_severity_override() {
+        let mut state = State::new(config::Repository {
+            subject_max_length: Some(10),
+            ..Default::default()
+        });
+        state.update_text("feat: too long subject");
+        let severities = |state: &State| -> Vec<_> {
+            state
+                .all_diagnostics()
+                .iter()
+                .map(|d| d.severity())
+                .collect()
+        };
+        assert_eq!(severities(&state), [DiagnosticSeverity::WARNING]);
+
+        state.set_config(config::Repository {
+            subject_max_length: Some(10),
+            severities: [(rule::SUBJECT_TOO_LONG.to_owned(), Severity::Error)].into(),
+            ..Default::default()
+        });
+        assert_eq!(severities(&state), [DiagnosticSeverity::ERROR]);
+
+        state.set_config(config::Repository {
+            subject_max_length: Some(10),
+            severities: [(rule::EMPTY_BODY.to_owned(), Severity::Hint)].into(),
+            ..Default::default()
+        });
+        assert_eq!(severities(&state), [DiagnosticSeverity::WARNING]);
+    }
+
+    #[test]
+    fn test_disabled_rule() {
+        let text = "feat: thing.\nbody";
+        let codes = |disabled_rules: Vec<String>| -> Vec<_> {
+            let mut state = State::new(config::Repository {
+                disabled_rules,
+                ..Default::default()
+            });
+            state.update_text(text);
+            state
+                .all_diagnostics()
+                .iter()
+                .filter_map(|d| d.report().code)
+                .collect()
+        };
+
+        assert_eq!(
+            codes(Vec::new()),
+            [rule::MISSING_BLANK_LINE, rule::TRAILING_PERIOD]
+        );
+        assert_eq!(
+            codes(vec![rule::MISSING_BLANK_LINE.to_owned()]),
+            [rule::TRAILING_PERIOD]
+        );
+    }
+
+    #[test]
+    fn test_subject_length_counts_graphemes() {
+        let mut state = State::new(config::Repository {
+            subject_max_length: Some(10),
+            ..Default::default()
+        });
+        state.update_text("feat: äöü👍🏽");
+
+        assert!(state.check_subject_length().is_none());
+    }
+
+    #[test]
+    fn test_subject_length_disabled() {
+        let mut state = State::new(config::Repository {
+            subject_max_length: Some(0),
+            ..Default::default()
+        });
+        state.update_text("feat: a subject that is much longer than it should be");
+
+        assert!(state.check_subject_length().is_none());
+    }
+
+    fn body_separator_diagnostic(text: &str) -> Option<String> {
+        let mut state = State::new(Default::default());
+        state.update_text(text);
+        state.check_body_separator().map(|d| d.inner.message)
+    }
+
+    #[test]
+    fn test_subject_only() {
+        assert_eq!(body_separator_diagnostic("feat: thing"), None);
+        assert_eq!(body_separator_diagnostic("feat: thing\n"), None);
+        assert_eq!(body_separator_diagnostic("feat: thing\n  \n"), None);
+        assert_eq!(
+            body_separator_diagnostic("feat: thing\n\n# Please enter the commit message"),
+            None
+        );
+        assert_eq!(
+            body_separator_diagnostic("feat: thing\n# Please enter the commit message"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_subject_and_body() {
+        assert_eq!(body_separator_diagnostic("feat: thing\n\nbody"), None);
+        assert_eq!(
+            body_separator_diagnostic("feat: thing\n\nbody\n\nmore body\n# comment"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_missing_blank_line() {
+        assert_eq!(
+            body_separator_diagnostic("feat: thing\nbody"),
+            Some("The second line should be empty!".to_owned())
+        );
+        assert_eq!(
+            body_separator_diagnostic("feat: thing\nbody\n\nmore body"),
+            Some("The second line should be empty!".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_empty_body_after_blank_line() {
+        assert_eq!(
+            body_separator_diagnostic("feat: thing\n\n\n  "),
+            Some("The body is empty, remove the blank lines or add a body".to_owned())
+        );
+    }
+
+    fn config_with_types(types: &[&str]) -> config::Repository {
+        config::Repository {
+            types: types
+                .iter()
+                .map(|&name| CommitElementDefinition {
+                    name: name.to_owned(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_merge_commit_is_not_linted() {
+        let mut state = State::new(config_with_types(&["feat"]));
+        state.update_text(
+            "Merge branch 'feature/a-very-long-branch-name-that-exceeds-the-limit' into main",
+        );
+
+        assert_eq!(state.result.kind, CommitKind::Merge);
+        assert!(state.all_diagnostics().is_empty());
+
+        state.set_config(config::Repository {
+            lint_merge_commits: true,
+            ..config_with_types(&["feat"])
+        });
+        let codes: Vec<_> = state
+            .all_diagnostics()
+            .into_iter()
+            .map(|d| lsp_types::Diagnostic::from(d).code)
+            .collect();
+        assert!(codes.contains(&Some(lsp_types::NumberOrString::String(
+            rule::INVALID_HEADER.to_owned()
+        ))));
+    }
+
+    #[test]
+    fn test_revert_commit() {
+        let (mut state, reverted) =
+            example("Revert \"|>feat: add a thing.<|\"\n\nThis reverts commit 1234abcd.");
+        state.set_config(config_with_types(&["feat"]));
+
+        assert_eq!(state.result.kind, CommitKind::Revert { reverted });
+        // neither the header format nor the trailing period of the reverted subject are flagged
+        assert!(state.all_diagnostics().is_empty());
+
+        let item = state.lookup(Position::new(0, 10)).unwrap();
+        assert!(matches!(item.kind, ItemKind::Revert));
+        assert_eq!(item.text, "feat: add a thing.");
+        assert_eq!(item.range, reverted);
+    }
+
+    #[test]
+    fn test_autosquash_commits() {
+        for header in [
+            "fixup! feat: add a thing.",
+            "squash! feat: add a thing.",
+            "amend! feat: add a thing.",
+            "fixup! fixup! feat: add a thing.",
+        ] {
+            let mut state = State::new(config_with_types(&["feat"]));
+            state.update_text(header);
+
+            assert_eq!(state.result.kind, CommitKind::Autosquash, "{header}");
+            let ty = state.result.ty.expect("the rest of the header is parsed");
+            assert_eq!(state.get_text(ty), "feat");
+            assert!(state.all_diagnostics().is_empty(), "{header}");
+        }
+    }
+
+    #[test]
+    fn test_autosquash_body_is_linted() {
+        let mut state = State::new(config_with_types(&["feat"]));
+        state.update_text("amend! feat: add a thing\nnew body");
+
+        let codes: Vec<_> = state
+            .all_diagnostics()
+            .into_iter()
+            .filter_map(|d| d.report().code)
+            .collect();
+        assert_eq!(codes, [rule::MISSING_BLANK_LINE]);
+    }
+
+    #[test]
+    fn test_autosquash_gitmoji() {
+        let mut state = State::new(config::Repository {
+            header_style: HeaderStyle::Gitmoji,
+            ..config_with_types(&["feat"])
+        });
+        state.update_text("fixup! ✨ feat: add a thing");
+
+        assert_eq!(state.result.gitmoji, Some(partial_line(0, 7..8)));
+        assert_eq!(state.get_text(state.result.ty.unwrap()), "feat");
+    }
+
+    #[test]
+    fn test_regular_commit_is_linted() {
+        let mut state = State::new(config_with_types(&["feat"]));
+        state.update_text("Merged the branch");
+
+        assert_eq!(state.result.kind, CommitKind::Regular);
+        let codes: Vec<_> = state
+            .all_diagnostics()
+            .into_iter()
+            .map(|d| lsp_types::Diagnostic::from(d).code)
+            .collect();
+        assert_eq!(
+            codes,
+            [Some(lsp_types::NumberOrString::String(
+                rule::INVALID_HEADER.to_owned()
+            ))]
+        );
+    }
+
+    #[test]
+    fn test_known_commit_type() {
+        let mut state = State::new(config_with_types(&["feat", "fix"]));
+        state.update_text("fix: thing");
+
+        assert!(state.check_commit_type().is_none());
+    }
+
+    #[test]
+    fn test_unknown_commit_type() {
+        let mut state = State::new(config_with_types(&["feat", "fix", "docs"]));
+        state.update_text("fety: thing");
+
+        let diag = state.check_commit_type().unwrap();
+        assert_eq!(state.get_text(diag.inner.range), "fety");
+        assert_eq!(
+            diag.inner.message,
+            "Unknown commit type 'fety', expected one of: feat, fix, docs"
+        );
+        assert_eq!(diag.inner.severity, Some(DiagnosticSeverity::WARNING));
+    }
+
+    #[test]
+    fn test_sample_reference_keys() {
+        let sample = "Refs #123, PROJ-45";
+        assert_eq!(
+            sample_reference_keys(&Default::default(), sample).unwrap(),
+            ["123"]
+        );
+
+        let jira = config::Repository {
+            issue_reference_pattern: Some("PROJ-([0-9]+)".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(sample_reference_keys(&jira, sample).unwrap(), ["45"]);
+
+        let invalid = config::Repository {
+            issue_reference_pattern: Some("PROJ-([0-9]+".to_owned()),
+            ..Default::default()
+        };
+        assert!(sample_reference_keys(&invalid, sample).is_err());
+    }
+
+    #[test]
+    fn test_set_config() {
+        let mut state = State::new(config_with_types(&["feat"]));
+        state.update_text("fety: thing #1");
+        assert!(state.check_commit_type().is_some());
+
+        state.set_config(config::Repository {
+            issue_reference_pattern: Some("thing".to_owned()),
+            ..config_with_types(&["fety"])
+        });
+
+        assert!(state.check_commit_type().is_none());
+        let references: Vec<_> = state.references().map(|r| r.text.as_str()).collect();
+        assert_eq!(references, ["thing"]);
+    }
+
+    #[test]
+    fn test_commit_type_unchecked_without_config() {
+        let (state, _) = example("|>fety: thing<|");
+
+        assert!(state.check_commit_type().is_none());
+    }
+
+    #[test]
+    fn test_default_types_are_not_checked() {
+        // a repository without config file gets the default types
+        let missing = std::env::temp_dir().join("commit-lsp-no-such-config.toml");
+        let config = config::Repository::reload(Some(&missing)).unwrap();
+        assert!(!config.types.is_empty());
+
+        let mut state = State::new(config);
+        state.update_text("wip: something");
+        assert!(state.check_commit_type().is_none());
+        assert!(state.all_diagnostics().is_empty());
+    }
+
+    fn config_with_scopes(scopes: &[&str]) -> config::Repository {
+        config::Repository {
+            scopes: config_with_types(scopes).types,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_known_commit_scope() {
+        let mut state = State::new(config_with_scopes(&["lsp", "cli"]));
+        state.update_text("fix(cli): thing");
+
+        assert!(state.check_commit_scope().is_none());
+    }
+
+    #[test]
+    fn test_unknown_commit_scope() {
+        let mut state = State::new(config_with_scopes(&["lsp", "cli"]));
+        state.update_text("fix(parser): thing");
+
+        let diag = state.check_commit_scope().unwrap();
+        assert_eq!(state.get_text(diag.inner.range), "parser");
+        assert_eq!(
+            diag.inner.message,
+            "Unknown commit scope 'parser', expected one of: lsp, cli"
+        );
+    }
+
+    fn scope_path_diagnostic(staged: &[&str], text: &str) -> Option<String> {
+        let mut state = State::new(config::Repository {
+            scopes: vec![CommitElementDefinition {
+                name: "api".to_owned(),
+                paths: vec!["src/api".into()],
+                ..Default::default()
+            }],
+            check_scope_matches_staged: true,
+            ..Default::default()
+        });
+        state.set_staged_files(staged.iter().map(PathBuf::from).collect());
+        state.update_text(text);
+        state
+            .check_scope_matches_staged()
+            .map(|d| state.get_text(d.inner.range))
+    }
+
+    #[test]
+    fn test_scope_matches_staged() {
+        assert_eq!(
+            scope_path_diagnostic(&["src/api/routes.rs", "README.md"], "fix(api): thing"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_scope_does_not_match_staged() {
+        assert_eq!(
+            scope_path_diagnostic(&["src/cli.rs"], "fix(api): thing").as_deref(),
+            Some("api")
+        );
+    }
+
+    #[test]
+    fn test_scope_matches_staged_ignores_unknown() {
+        assert_eq!(scope_path_diagnostic(&[], "fix(api): thing"), None);
+        assert_eq!(
+            scope_path_diagnostic(&["src/cli.rs"], "fix(deps): bump"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_dependency_scopes_are_builtin() {
+        let mut state = State::new(config_with_scopes(&["lsp", "cli"]));
+
+        state.update_text("feat(deps): bump regex");
+        assert!(state.check_commit_scope().is_none());
+
+        state.update_text("chore(deps-dev): bump wiremock");
+        assert!(state.check_commit_scope().is_none());
+    }
+
+    #[test]
+    fn test_rank_scopes_by_changed_files() {
+        let mut config = config_with_scopes(&["cli", "lsp", "docs"]);
+        config.scopes[1].paths = vec!["src/lsp".into(), "src/lsp.rs".into()];
+        config.scopes[2].paths = vec!["doc".into()];
+        let state = State::new(config);
+
+        let ranked: Vec<_> = state
+            .rank_commit_scopes(&["src/lsp/hover.rs".into(), "src/lspx.rs".into()])
+            .into_iter()
+            .map(|(s, matches)| (s.name.as_str(), matches))
+            .collect();
+
+        assert_eq!(ranked, [("lsp", true), ("cli", false), ("docs", false)]);
+    }
+
+    #[test]
+    fn test_missing_commit_scope_is_fine() {
+        let mut state = State::new(config_with_scopes(&["lsp", "cli"]));
+        state.update_text("fix: thing");
+
+        assert!(state.check_commit_scope().is_none());
+    }
+
+    #[test]
+    fn test_commit_scope_unchecked_without_config() {
+        let (state, _) = example("|>fix(parser): thing<|");
+
+        assert!(state.check_commit_scope().is_none());
+    }
+
+    #[test]
+    fn test_empty_subject() {
+        let (state, range) = example("fix|>: <|");
+        let diag = state.check_empty_subject().unwrap();
+        assert_eq!(diag.inner.range, range);
+
+        let (state, range) = example("fix(ui)|>:    <|\n\nBody");
+        let diag = state.check_empty_subject().unwrap();
+        assert_eq!(diag.inner.range, range);
+
+        let (state, _) = example("|>fix: thing<|");
+        assert!(state.check_empty_subject().is_none());
+    }
+
+    fn body_required_diagnostic(text: &str) -> Option<Diagnostic> {
+        let mut state = State::new(config::Repository {
+            types_requiring_body: vec!["feat".to_owned(), "fix".to_owned()],
+            ..Default::default()
+        });
+        state.update_text(text);
+        state.check_body_required()
+    }
+
+    #[test]
+    fn test_feat_without_body() {
+        let (_, range) = example("|>feat: add thing<|");
+        let diag = body_required_diagnostic("feat: add thing").unwrap();
+        assert_eq!(diag.inner.range, range);
+
+        let diag = body_required_diagnostic("feat: add thing\n\n# comment\nRefs: #1\n").unwrap();
+        assert_eq!(diag.inner.range, range);
+        assert!(body_required_diagnostic("feat: add thing\n\n  \n").is_some());
+    }
+
+    fn scope_required_diagnostic(types: &[&str], text: &str) -> Option<Diagnostic> {
+        let mut state = State::new(config::Repository {
+            types_requiring_scope: types.iter().map(|&t| t.to_owned()).collect(),
+            ..Default::default()
+        });
+        state.update_text(text);
+        state.check_scope_required()
+    }
+
+    #[test]
+    fn test_unscoped_feat() {
+        let (_, range) = example("|>feat<|: add thing");
+        let diag = scope_required_diagnostic(&["feat"], "feat: add thing").unwrap();
+        assert_eq!(diag.inner.range, range);
+
+        let (_, range) = example("|>feat!<|: add thing");
+        let diag = scope_required_diagnostic(&["feat"], "feat!: add thing").unwrap();
+        assert_eq!(diag.inner.range, range);
+    }
+
+    #[test]
+    fn test_scoped_feat() {
+        assert!(scope_required_diagnostic(&["feat"], "feat(ui): add thing").is_none());
+        assert!(scope_required_diagnostic(&["feat"], "docs: add thing").is_none());
+        assert!(scope_required_diagnostic(&[], "feat: add thing").is_none());
+    }
+
+    #[test]
+    fn test_feat_with_body() {
+        assert!(body_required_diagnostic("feat: add thing\n\nBecause it was missing.").is_none());
+        assert!(body_required_diagnostic("docs: add thing").is_none());
+        assert!(body_required_diagnostic("chore(deps): bump\n\nRefs: #1").is_none());
+    }
+
+    #[test]
+    fn test_trailing_period() {
+        let (state, range) = example("feat: implement the thing|>.<|");
+
+        let diag = state.check_trailing_period().unwrap();
+        assert_eq!(diag.inner.range, range);
     }
 
-    pub fn update_text(&mut self, new_text: &str) {
-        self.lines = new_text.lines().map(ToOwned::to_owned).collect();
+    #[test]
+    fn test_utf16_positions() {
+        // the emoji is a single character, but two UTF-16 code units
+        let mut state = State::new(Default::default());
+        state.update_text("fix: 😀 crash #1.");
 
-        if let Some(header) = self.lines.first() {
-            if let Some((ty, scope, _breaking)) = parse_header(header) {
-                self.ty = Some(self.partial_line(0, substr_offset(header, ty)));
+        let period = state.check_trailing_period().unwrap().inner.range;
+        assert_eq!(period, partial_line(0, 15..16));
+        let item = state.lookup(Position::new(0, 14)).unwrap();
+        assert!(matches!(item.kind, ItemKind::Ref(_)));
+        assert_eq!(item.range, partial_line(0, 13..15));
 
-                self.scope = scope.map(|txt| self.partial_line(0, substr_offset(header, txt)));
-            }
-        }
+        state.set_position_encoding(PositionEncoding::Utf16);
+        let period = state.check_trailing_period().unwrap().inner.range;
+        assert_eq!(period, partial_line(0, 16..17));
+        let item = state.lookup(Position::new(0, 15)).unwrap();
+        assert!(matches!(item.kind, ItemKind::Ref(_)));
+        assert_eq!(item.range, partial_line(0, 14..16));
+        assert_eq!(state.get_text(partial_line(0, 5..7)), "😀");
+
+        state.apply_change(partial_line(0, 7..7), "!");
+        assert_eq!(state.text, "fix: 😀! crash #1.");
     }
 
-    pub fn all_diagnostics(&self) -> Vec<Diagnostic> {
-        let mut diagnostics = Vec::new();
+    #[test]
+    fn test_position_encoding_offsets() {
+        let line = "a😀b";
+        assert_eq!(PositionEncoding::Utf16.text_len(line), 4);
+        assert_eq!(PositionEncoding::Utf32.text_len(line), 3);
+        assert_eq!(PositionEncoding::Utf16.byte_offset(line, 3), Some(5));
+        assert_eq!(PositionEncoding::Utf32.byte_offset(line, 2), Some(5));
+        // inside the surrogate pair
+        assert_eq!(PositionEncoding::Utf16.byte_offset(line, 2), Some(5));
+        assert_eq!(PositionEncoding::Utf16.byte_offset(line, 5), None);
+    }
 
-        if self.lines.len() > 1 && !self.lines[1].is_empty() {
-            diagnostics.push(Diagnostic::new(
-                self.full_line(1),
-                "The second line should be empty!",
-            ));
-        }
+    #[test]
+    fn test_trailing_ellipsis_is_allowed() {
+        let (state, _) = example("|>feat: implement the thing...<|");
 
-        diagnostics
+        assert!(state.check_trailing_period().is_none());
     }
 
-    /// Look at the given position in the text and return the element there.
-    /// Returns `None` for out of bounds accesses and if there is nothing special there.
-    pub fn lookup(&self, pos: Position) -> Option<Item> {
-        let cursor = pos.character as usize;
-        let line = self.lines.get(pos.line as usize)?;
+    #[test]
+    fn test_no_trailing_period() {
+        let (state, _) = example("|>feat: implement v1.0<|");
 
-        // find word under cursor
-        let start = line[..cursor]
-            .rfind(|c: char| !c.is_alphanumeric() && c != '#')
-            .map(|i| i + 1)
-            .unwrap_or(0);
+        assert!(state.check_trailing_period().is_none());
+    }
 
-        let end = line[cursor..]
-            .find(|c: char| !c.is_alphanumeric() && c != '#')
-            .map(|i| i + cursor)
-            .unwrap_or(line.len());
+    fn imperative_mood_diagnostic(text: &str) -> Option<String> {
+        let mut state = State::new(config::Repository {
+            check_imperative_mood: true,
+            ..Default::default()
+        });
+        state.update_text(text);
+        state
+            .check_imperative_mood()
+            .map(|d| state.get_text(d.inner.range))
+    }
 
-        if start == end {
-            return None;
-        }
+    #[test]
+    fn test_imperative_mood() {
+        assert_eq!(imperative_mood_diagnostic("fix: fix the bug"), None);
+        assert_eq!(imperative_mood_diagnostic("feat: embed the font"), None);
+        assert_eq!(imperative_mood_diagnostic("feat(ui): add feature"), None);
+    }
 
-        let range = self.partial_line(pos.line, start..end);
-        let text = self.get_text(range);
-        info!(text, "Found word under cursor");
+    #[test]
+    fn test_non_imperative_mood() {
+        assert_eq!(
+            imperative_mood_diagnostic("fix: fixed the bug"),
+            Some("fixed".to_owned())
+        );
+        assert_eq!(
+            imperative_mood_diagnostic("feat(ui): Adding feature"),
+            Some("Adding".to_owned())
+        );
+    }
 
-        let kind = {
-            let ticket_regex = regex!(r"#([0-9]+)");
-            if let Some(caps) = ticket_regex.captures(&text) {
-                let Ok(id) = caps
-                    .get(1)
-                    .expect("There should be one capture")
-                    .as_str()
-                    .parse()
-                else {
-                    return None;
-                };
-                ItemKind::Ref(id)
-            } else if Some(range) == self.ty {
-                ItemKind::Ty
-            } else if Some(range) == self.scope {
-                ItemKind::Scope
-            } else {
-                // TODO(texel, 2024-05-19): determine other types
-                return None;
-            }
-        };
+    #[test]
+    fn test_imperative_mood_disabled_by_default() {
+        let (state, _) = example("|>fix: fixed the bug<|");
 
-        Some(Item { kind, text, range })
+        assert!(state.check_imperative_mood().is_none());
     }
 
-    pub fn commit_type_info(&self) -> Option<CommitElementDefinition> {
-        let ty = self.get_text(self.ty?);
-        self.config.types.iter().find(|t| t.name == ty).cloned()
+    fn ascii_subject_diagnostics(text: &str) -> Vec<String> {
+        let mut state = State::new(config::Repository {
+            ascii_only_subject: true,
+            ..Default::default()
+        });
+        state.update_text(text);
+        state
+            .check_ascii_subject()
+            .into_iter()
+            .map(|d| state.get_text(d.inner.range))
+            .collect()
     }
 
-    pub fn commit_scope_info(&self) -> Option<CommitElementDefinition> {
-        let ty = self.get_text(self.scope?);
-        self.config.scopes.iter().find(|t| t.name == ty).cloned()
+    #[test]
+    fn test_ascii_subject() {
+        assert!(ascii_subject_diagnostics("fix: plain ascii\n\nbody with ümlauts").is_empty());
     }
 
-    pub fn get_commit_types(&self) -> &[CommitElementDefinition] {
-        &self.config.types
+    #[test]
+    fn test_non_ascii_subject() {
+        assert_eq!(
+            ascii_subject_diagnostics("fix: naïve café 🎉🎉"),
+            ["ï", "é", "🎉🎉"]
+        );
     }
 
-    pub fn get_commit_scopes(&self) -> &[CommitElementDefinition] {
-        &self.config.scopes
+    #[test]
+    fn test_ascii_subject_disabled_by_default() {
+        let (state, _) = example("|>fix: naïve café<|");
+
+        assert!(state.check_ascii_subject().is_empty());
     }
 
-    fn full_line(&self, idx: u32) -> Range {
-        Range::new(
-            Position::new(idx, 0),
-            Position::new(idx, self.lines[idx as usize].len() as u32),
-        )
+    fn reference_keyword_diagnostics(text: &str) -> Vec<String> {
+        let mut state = State::new(config::Repository {
+            reference_keywords: vec!["Closes".to_owned(), "Refs".to_owned()],
+            ..Default::default()
+        });
+        state.update_text(text);
+        state
+            .check_reference_keywords()
+            .into_iter()
+            .map(|d| state.get_text(d.inner.range))
+            .collect()
     }
 
-    fn partial_line(&self, line: u32, range: std::ops::Range<usize>) -> Range {
-        Range::new(
-            Position::new(line, range.start as u32),
-            Position::new(line, range.end as u32),
-        )
+    #[test]
+    fn test_reference_keywords_accepted() {
+        let diags = reference_keyword_diagnostics("fix: thing\n\nCloses: #1\nrefs #2");
+
+        assert!(diags.is_empty());
     }
 
-    /// Returns the commit text inside the given range.
-    fn get_text(&self, range: Range) -> String {
-        // range.end.line is inclusive
-        let line_range = (range.start.line as usize)..=(range.end.line as usize);
+    #[test]
+    fn test_reference_keywords_bare_reference() {
+        let diags = reference_keyword_diagnostics("fix: thing #1\n\nSee #2\nFixes: #3\nRefs: #4");
 
-        let lines = &self.lines[line_range];
+        assert_eq!(diags, ["#1", "#2", "#3"]);
+    }
 
-        // count bytes preceding the last line, taking newlines into account
-        let offset: usize = lines
-            .iter()
-            .take(lines.len() - 1) // do not count line containing "range.end"
-            .map(|l| l.len() + 1) // + 1 for the newlines we will add
-            .sum();
+    #[test]
+    fn test_reference_keywords_disabled_by_default() {
+        let (state, _) = example("|>fix: thing #1<|");
 
-        let text = lines.join("\n");
+        assert!(state.check_reference_keywords().is_empty());
+    }
 
-        let char_range = (range.start.character as usize)..(range.end.character as usize + offset);
+    #[test]
+    fn test_is_empty() {
+        let (state, _) = example("|><|\n# Please enter the commit message\n#\n");
+        assert!(state.is_empty());
 
-        text[char_range].to_owned()
+        let (state, _) = example("|>#123<|\n# Please enter the commit message\n");
+        assert!(!state.is_empty());
     }
-}
 
-fn parse_header(first_line: &str) -> Option<(&str, Option<&str>, bool)> {
-    let header_format =
-        regex!(r#"(?P<ty>[a-z]+)(?:\((?P<scope>[^)]+)\))?(?P<breaking>!)?: (?P<subject>.*)$"#);
+    #[test]
+    fn test_failure_threshold() {
+        let error = Diagnostic::new(Range::default(), "error");
+        let warning =
+            Diagnostic::new(Range::default(), "warning").with_severity(DiagnosticSeverity::WARNING);
+        let hint =
+            Diagnostic::new(Range::default(), "hint").with_severity(DiagnosticSeverity::HINT);
 
-    let captures = header_format.captures(first_line)?;
+        assert!(error.fails(FailureThreshold::Error));
+        assert!(!warning.fails(FailureThreshold::Error));
+        assert!(warning.fails(FailureThreshold::Warning));
+        assert!(!hint.fails(FailureThreshold::Warning));
+    }
 
-    let ty = captures.name("ty")?.as_str();
-    let scope = captures.name("scope").map(|m| m.as_str());
-    let breaking = captures.name("breaking").is_some();
+    #[test]
+    fn test_detect_line_ending() {
+        assert_eq!(LineEnding::detect("feat: a\n\nbody"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("feat: a\r\n\r\nbody"), LineEnding::CrLf);
+        assert_eq!(LineEnding::detect("feat: a"), LineEnding::Lf);
+    }
 
-    Some((ty, scope, breaking))
-}
+    #[test]
+    fn test_normalize_line_ending() {
+        assert_eq!(LineEnding::CrLf.normalize("a\nb\r\nc"), "a\r\nb\r\nc");
+        assert_eq!(LineEnding::Lf.normalize("a\nb\r\nc\n"), "a\nb\nc\n");
+    }
 
-/// Returns the offset of a string slice in another string slice.
-/// The second slice **MUST** point into part of the first.
-fn substr_offset<'needle, 'haystack: 'needle>(
-    container: &'haystack str,
-    contained: &'needle str,
-) -> std::ops::Range<usize> {
-    let delta = unsafe { contained.as_ptr().offset_from(container.as_ptr()) };
-    assert!(delta >= 0);
+    #[test]
+    fn test_edits_use_document_line_ending() {
+        let mut state = State::new(Default::default());
+        state.update_text("feat: a\r\n\r\nbody\r\n");
 
-    let delta = delta as usize;
-    assert!(delta + contained.len() <= container.len());
+        let edit = state.text_edit(Range::default(), "Signed-off-by: me\n");
+        assert_eq!(edit.new_text, "Signed-off-by: me\r\n");
+    }
 
-    delta..delta + contained.len()
-}
+    #[test]
+    fn test_substring_offset_works() {
+        let outer = "Hello World!";
+        let inner = &outer[6..];
 
-pub struct Diagnostic {
-    inner: lsp_types::Diagnostic,
-}
+        assert_eq!(substr_offset(outer, inner), 6..12);
+    }
 
-impl Diagnostic {
-    pub fn new(range: Range, message: impl ToString) -> Self {
-        Self {
-            inner: lsp_types::Diagnostic {
-                range,
-                severity: None,
-                code: None,
-                code_description: None,
-                source: None,
-                message: message.to_string(),
-                related_information: None,
-                tags: None,
-                data: None,
-            },
+    fn quick_fix(config: config::Repository, text: &str, code: &str) -> Option<String> {
+        let mut state = State::new(config);
+        state.update_text(text);
+
+        let diag = state
+            .all_diagnostics()
+            .into_iter()
+            .map(lsp_types::Diagnostic::from)
+            .find(|d| d.code == Some(lsp_types::NumberOrString::String(code.to_owned())))?;
+        let (_, edits) = state.quick_fixes(&diag).into_iter().next()?;
+        for edit in edits {
+            state.apply_change(edit.range, &edit.new_text);
         }
-    }
-}
 
-impl From<Diagnostic> for lsp_types::Diagnostic {
-    fn from(value: Diagnostic) -> Self {
-        value.inner
+        Some(state.text)
     }
-}
 
-impl Display for Diagnostic {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let line = self.inner.range.start.line + 1;
-        let col = self.inner.range.start.character + 1;
-        let msg = &self.inner.message;
-        write!(f, "[{line}:{col}] {msg}")
+    #[test]
+    fn test_fix_trailing_period() {
+        assert_eq!(
+            quick_fix(Default::default(), "fix: thing.", rule::TRAILING_PERIOD).as_deref(),
+            Some("fix: thing")
+        );
     }
-}
-
-pub struct Item {
-    pub kind: ItemKind,
-    pub text: String,
-    pub range: Range,
-}
-
-/// An item of interest in the commit text.
-pub enum ItemKind {
-    /// The commit type (e.g. `feat`, `fix`)
-    Ty,
-    /// The commit scope
-    Scope,
-    /// A reference to a ticket/issue/etc
-    Ref(u64),
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    #[test]
+    fn test_fix_type_case() {
+        assert_eq!(
+            quick_fix(
+                config_with_types(&["feat", "fix"]),
+                "Fix: thing",
+                rule::UNKNOWN_TYPE
+            )
+            .as_deref(),
+            Some("fix: thing")
+        );
+        assert_eq!(
+            quick_fix(
+                config_with_types(&["feat", "fix"]),
+                "Bug: thing",
+                rule::UNKNOWN_TYPE
+            ),
+            None
+        );
+    }
 
-    /// Parse a text example with embedded range markers.
-    ///
-    /// `|>` will mark the start of the range and `<|` marks the end.
-    ///
-    /// Example input string:
-    /// ```gitcommit
-    /// The range |>is this part<| of the text.
-    /// ```
-    fn text_with_range(text: &str) -> (String, Range) {
-        let mut iter = text
-            .lines()
-            .enumerate()
-            .filter(|(_, l)| l.contains("|>") || l.contains("<|"));
+    #[test]
+    fn test_fix_missing_blank_line() {
+        assert_eq!(
+            quick_fix(
+                Default::default(),
+                "fix: thing\r\nbody\r\n",
+                rule::MISSING_BLANK_LINE
+            )
+            .as_deref(),
+            Some("fix: thing\r\n\r\nbody\r\n")
+        );
+    }
 
-        let (mut idx, mut line) = iter.next().unwrap();
-        let begin = {
-            let char = line.find("|>").unwrap();
-            Position::new(idx as u32, char as u32)
+    #[test]
+    fn test_body_line_length() {
+        let config = config::Repository {
+            body_max_line_length: Some(20),
+            ..Default::default()
         };
+        let mut state = State::new(config);
+        state.update_text(
+            "fix: thing\n\nthis line is too long for the limit\nshort line\n\n\
+             Signed-off-by: A very long trailer <a@example.com>",
+        );
+        let ranges: Vec<_> = state
+            .check_body_line_length()
+            .into_iter()
+            .map(|d| lsp_types::Diagnostic::from(d).range)
+            .collect();
+        assert_eq!(ranges, [partial_line(2, 20..35)]);
 
-        let end = {
-            let single_line = line.contains("<|");
-            if !single_line {
-                (idx, line) = iter.next().unwrap();
-            }
-
-            let mut char = line.find("<|").unwrap();
+        state.config.body_max_line_length = None;
+        assert!(state.check_body_line_length().is_empty());
+    }
 
-            if single_line {
-                // skip the `|>` that precedes us in single line mode
-                char -= 2;
-            }
-            Position::new(idx as u32, char as u32)
+    #[test]
+    fn test_fix_body_line_length() {
+        let config = config::Repository {
+            body_max_line_length: Some(20),
+            ..Default::default()
         };
+        assert_eq!(
+            quick_fix(
+                config.clone(),
+                "fix: thing\n\n  this line is too long for the limit\nnext",
+                rule::BODY_LINE_TOO_LONG
+            )
+            .as_deref(),
+            Some("fix: thing\n\n  this line is too\n  long for the limit\nnext")
+        );
+        assert_eq!(
+            quick_fix(
+                config.clone(),
+                "fix: thing\n\nüber größe änderung längen",
+                rule::BODY_LINE_TOO_LONG
+            )
+            .as_deref(),
+            Some("fix: thing\n\nüber größe änderung\nlängen")
+        );
+        assert_eq!(
+            quick_fix(
+                config,
+                "fix: thing\n\nhttps://example.com/a/very/long/url",
+                rule::BODY_LINE_TOO_LONG
+            ),
+            None
+        );
+    }
 
-        assert_eq!(iter.next(), None);
+    #[test]
+    fn test_no_body_line_fix_without_limit() {
+        let mut state = State::new(config::Repository {
+            body_max_line_length: Some(20),
+            ..Default::default()
+        });
+        state.update_text("fix: thing\n\nthis line is too long for the limit");
+        let diag = lsp_types::Diagnostic::from(state.check_body_line_length().remove(0));
 
-        (
-            text.replace("|>", "").replace("<|", ""),
-            Range::new(begin, end),
-        )
+        // the client still knows the diagnostic after the limit was removed
+        state.set_config(Default::default());
+        assert!(state.quick_fixes(&diag).is_empty());
     }
 
-    fn example(text: &str) -> (State, Range) {
-        let (text, range) = text_with_range(text);
-        let mut state = State::new(Default::default());
-        state.update_text(&text);
-        (state, range)
+    #[test]
+    fn test_diagnostics_have_code_and_source() {
+        let mut state = State::new(config::Repository {
+            check_imperative_mood: true,
+            require_sign_off: true,
+            ..config_with_types(&["feat"])
+        });
+        state.update_text("fox: added things.\nbody");
+
+        let diagnostics = state.all_diagnostics();
+        assert!(!diagnostics.is_empty());
+        for diag in diagnostics {
+            let diag = lsp_types::Diagnostic::from(diag);
+            assert!(diag.code.is_some(), "{} has no code", diag.message);
+            assert_eq!(diag.source.as_deref(), Some("commit-lsp"));
+        }
     }
 
     #[test]
-    fn test_get_text_single_line() {
-        let (state, range) = example("this |>is a<| test");
+    fn test_diagnostic_display() {
+        let diag = Diagnostic::new(partial_line(0, 4..5), "message").with_code(rule::EMPTY_BODY);
 
-        assert_eq!(state.get_text(range), "is a");
+        assert_eq!(diag.to_string(), "[1:5] message [empty-body]");
     }
 
-    #[test]
-    fn test_get_text_full_single_line() {
-        let (state, range) = example("|>this is a test<|");
+    fn format(config: config::Repository, text: &str) -> String {
+        let mut state = State::new(config);
+        state.update_text(text);
+        let formatted = state.formatted_text();
 
-        assert_eq!(state.get_text(range), "this is a test");
+        // formatting is idempotent
+        state.update_text(&formatted);
+        assert_eq!(state.formatted_text(), formatted);
+        assert!(state.format().is_none());
+
+        formatted
     }
 
     #[test]
-    fn test_get_text_full_empty_range() {
-        let (state, range) = example("this is|><| a test");
+    fn test_format_whitespace() {
+        assert_eq!(
+            format(
+                Default::default(),
+                "fix: thing  \n\n\n\nbody \nmore\t\n\n\n"
+            ),
+            "fix: thing\n\nbody\nmore\n"
+        );
+    }
 
-        assert_eq!(state.get_text(range), "");
+    #[test]
+    fn test_format_inserts_blank_line() {
+        assert_eq!(
+            format(Default::default(), "fix: thing\r\nbody"),
+            "fix: thing\r\n\r\nbody"
+        );
     }
 
     #[test]
-    fn test_get_text_multi_line() {
-        let (state, range) = example("this is a |>test\nover two<| lines");
+    fn test_format_subject_only() {
+        assert_eq!(format(Default::default(), "fix: thing\n\n"), "fix: thing\n");
+        assert_eq!(format(Default::default(), ""), "");
+    }
 
-        assert_eq!(state.get_text(range), "test\nover two");
+    #[test]
+    fn test_format_lowercase_type() {
+        let config = config::Repository {
+            format_lowercase_type: true,
+            ..Default::default()
+        };
+        assert_eq!(format(config, "Fix(Lsp): Thing"), "fix(Lsp): Thing");
+        assert_eq!(
+            format(Default::default(), "Fix(Lsp): Thing"),
+            "Fix(Lsp): Thing"
+        );
     }
 
     #[test]
-    fn test_parse_header_with_scope() {
-        let example = "feat(lsp): implement the thing";
+    fn test_format_edit() {
+        let mut state = State::new(Default::default());
+        state.update_text("fix: thing \n\nbody\n");
 
-        let (ty, scope, breaking) = parse_header(example).unwrap();
+        let edit = state.format().unwrap();
+        assert_eq!(
+            edit.range,
+            Range::new(Position::new(0, 0), Position::new(3, 0))
+        );
+        assert_eq!(edit.new_text, "fix: thing\n\nbody\n");
+    }
 
-        assert_eq!(ty, "feat");
-        assert_eq!(scope, Some("lsp"));
-        assert_eq!(breaking, false);
+    fn symbol_names(text: &str) -> Vec<(String, Vec<String>)> {
+        let mut state = State::new(Default::default());
+        state.update_text(text);
+        state
+            .document_symbols()
+            .into_iter()
+            .map(|s| {
+                let children = s.children.unwrap_or_default();
+                (s.name, children.into_iter().map(|c| c.name).collect())
+            })
+            .collect()
     }
 
     #[test]
-    fn test_parse_header_without_scope() {
-        let example = "feat: implement the thing";
-
-        let (ty, scope, breaking) = parse_header(example).unwrap();
+    fn test_document_symbols() {
+        let symbols = symbol_names(
+            "feat(lsp): add outline\n\nSome body\n\nmore body\n\nRefs: #1\nSigned-off-by: me\n",
+        );
 
-        assert_eq!(ty, "feat");
-        assert_eq!(scope, None);
-        assert_eq!(breaking, false);
+        assert_eq!(
+            symbols,
+            [
+                (
+                    "feat(lsp): add outline".to_owned(),
+                    vec![
+                        "feat".to_owned(),
+                        "lsp".to_owned(),
+                        "add outline".to_owned()
+                    ]
+                ),
+                ("Body".to_owned(), vec![]),
+                ("Refs".to_owned(), vec![]),
+                ("Signed-off-by".to_owned(), vec![]),
+            ]
+        );
     }
 
     #[test]
-    fn test_parse_header_with_scope_and_breaking_change() {
-        let example = "feat(lsp)!: implement the thing";
+    fn test_semantic_tokens() {
+        let mut state = State::new(Default::default());
+        state.update_text("feat(lsp)!: highlight #1 and more\n\nRefs: #2\n");
 
-        let (ty, scope, breaking) = parse_header(example).unwrap();
+        let tokens: Vec<_> = state
+            .semantic_tokens()
+            .into_iter()
+            .map(|(range, ty)| (state.get_text(range), ty))
+            .collect();
 
-        assert_eq!(ty, "feat");
-        assert_eq!(scope, Some("lsp"));
-        assert_eq!(breaking, true);
+        assert_eq!(
+            tokens,
+            [
+                ("feat".to_owned(), SemanticTokenType::KEYWORD),
+                ("lsp".to_owned(), SemanticTokenType::NAMESPACE),
+                ("!".to_owned(), SemanticTokenType::OPERATOR),
+                ("highlight ".to_owned(), SemanticTokenType::STRING),
+                ("#1".to_owned(), SemanticTokenType::NUMBER),
+                (" and more".to_owned(), SemanticTokenType::STRING),
+                ("Refs".to_owned(), SemanticTokenType::PROPERTY),
+                ("#2".to_owned(), SemanticTokenType::NUMBER),
+            ]
+        );
+        assert_eq!(
+            state.semantic_tokens()[0].0,
+            Range::new(Position::new(0, 0), Position::new(0, 4))
+        );
     }
 
     #[test]
-    fn test_substring_offset_works() {
-        let outer = "Hello World!";
-        let inner = &outer[6..];
+    fn test_document_symbols_single_line() {
+        let symbols = symbol_names("fix: thing");
 
-        assert_eq!(substr_offset(outer, inner), 6..12);
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(symbols[0].0, "fix: thing");
+
+        assert!(symbol_names("").is_empty());
     }
 }
@@ -1,16 +1,74 @@
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+
+use commit_lsp::config::FailureThreshold;
 
 #[derive(Parser)]
+#[clap(version)]
 pub struct Cli {
+    /// Read the repository configuration from this file
+    /// instead of `.commit-lsp.toml` in the root of the repository.
+    #[clap(long, global = true)]
+    pub config: Option<PathBuf>,
+
     #[clap(subcommand)]
     pub action: Action,
 }
 
 #[derive(Subcommand)]
 pub enum Action {
-    Run,
-    Lint { file: PathBuf },
+    #[cfg(feature = "issue-tracker")]
+    Run {
+        /// Only complete tickets updated within the given time span (e.g. `7d`, `12h`, `2w`).
+        #[clap(long, value_parser = commit_lsp::text_util::parse_duration)]
+        since: Option<std::time::Duration>,
+    },
+    Lint {
+        /// The file containing the commit message, or `-` to read it from stdin.
+        file: PathBuf,
+        /// The least severe kind of diagnostic that makes linting fail.
+        /// Overrides the `fail_on` setting of the repository configuration.
+        #[clap(long, value_enum)]
+        fail_on: Option<FailureThreshold>,
+        /// How to print the diagnostics.
+        #[clap(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    #[cfg(feature = "issue-tracker")]
     Checkhealth,
+    /// List the tickets available for completion.
+    #[cfg(feature = "issue-tracker")]
+    Tickets {
+        /// Print the tickets as JSON array.
+        #[clap(long)]
+        json: bool,
+        /// Only list tickets updated within the given time span (e.g. `7d`, `12h`, `2w`).
+        #[clap(long, value_parser = commit_lsp::text_util::parse_duration)]
+        since: Option<std::time::Duration>,
+    },
+    /// Print the LSP `initialize` result (server info and capabilities) as JSON.
+    #[cfg(feature = "issue-tracker")]
+    Capabilities,
+    /// Load the repository and user configuration and report problems,
+    /// without contacting the issue tracker.
+    CheckConfig,
+    /// Create an example `.commit-lsp.toml` in the root of the repository.
+    Init {
+        /// Also create the user configuration file.
+        #[clap(long)]
+        user: bool,
+        /// Overwrite existing files.
+        #[clap(long)]
+        force: bool,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One line per diagnostic.
+    #[default]
+    Text,
+    /// A JSON array of diagnostics, for other tools to consume.
+    Json,
 }
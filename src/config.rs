@@ -1,9 +1,17 @@
-use std::{fs::File, io::Read as _};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read as _,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use directories::ProjectDirs;
-use serde::Deserialize;
-use tracing::info;
+use serde::{de::DeserializeOwned, Deserialize};
+use tracing::{info, warn};
 
+#[cfg(feature = "issue-tracker")]
+use crate::issue_tracker::{RetryPolicy, Timeouts};
 use crate::{
     git::get_repo_root,
     healthcheck::{HealthReport, ResultExt},
@@ -11,19 +19,333 @@ use crate::{
 
 #[derive(Deserialize, Debug, Clone, Default)]
 pub struct User {
+    #[serde(default)]
     pub remotes: Vec<Remote>,
+
+    /// Milliseconds to wait after the last edit before querying the issue tracker.
+    pub fetch_cooldown_ms: Option<u64>,
+
+    /// Milliseconds to wait after the last edit before publishing diagnostics.
+    pub diagnostics_cooldown_ms: Option<u64>,
+
+    /// Show the titles of referenced tickets next to the references (default: true).
+    pub ticket_inlay_hints: Option<bool>,
+
+    /// Summarize the subject length and body size when a commit message is opened.
+    #[serde(default)]
+    pub show_open_summary: OpenSummary,
+
+    /// Personal commit types, in addition to the ones of the repository.
+    #[serde(default)]
+    pub types: Vec<CommitElementDefinition>,
+
+    /// Personal scopes, in addition to the ones of the repository.
+    #[serde(default)]
+    pub scopes: Vec<CommitElementDefinition>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct Remote {
     pub host: String,
+
+    /// The kind of issue tracker, detected from the host if not set.
+    pub issue_tracker_type: Option<IssueTrackerType>,
+
+    /// Command printing the access token to stdout.
+    #[serde(default)]
     pub credentials_command: Vec<String>,
+
+    /// Environment variable containing the access token, used if no `credentials_command` is
+    /// configured (default: `COMMIT_LSP_TOKEN`).
+    pub token_env: Option<String>,
+
+    /// How often a request is retried after a network problem or server error.
+    pub retries: Option<u32>,
+    /// Milliseconds to wait before the first retry, doubled for every further retry.
+    pub retry_delay_ms: Option<u64>,
+
+    /// Milliseconds to wait for a connection to the issue tracker (default: 10000).
+    pub connect_timeout_ms: Option<u64>,
+    /// Milliseconds to wait for a response of the issue tracker (default: 10000).
+    pub timeout_ms: Option<u64>,
+
+    /// Which issues are listed for completion: `open`, `closed` or both (default: `["open"]`).
+    #[serde(default)]
+    pub issue_states: Vec<String>,
+
+    /// Seconds to keep the output of the credentials command in memory.
+    /// Defaults to the lifetime of the process, 0 disables caching.
+    pub credentials_cache_seconds: Option<u64>,
 }
 
-#[derive(Deserialize, Debug, Clone, Default)]
+impl Remote {
+    #[cfg(feature = "issue-tracker")]
+    pub fn retry_policy(&self) -> RetryPolicy {
+        let default = RetryPolicy::default();
+        RetryPolicy {
+            retries: self.retries.unwrap_or(default.retries),
+            base_delay: self
+                .retry_delay_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.base_delay),
+        }
+    }
+
+    #[cfg(feature = "issue-tracker")]
+    pub fn timeouts(&self) -> Timeouts {
+        let default = Timeouts::default();
+        Timeouts {
+            connect: self
+                .connect_timeout_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.connect),
+            request: self
+                .timeout_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.request),
+        }
+    }
+
+    /// The configured issue states, together with all values that are not known.
+    pub fn issue_states(&self) -> (IssueStates, Vec<&str>) {
+        let mut states = IssueStates {
+            open: false,
+            closed: false,
+        };
+        let mut unknown = Vec::new();
+        for state in &self.issue_states {
+            match state.as_str() {
+                "open" => states.open = true,
+                "closed" => states.closed = true,
+                other => unknown.push(other),
+            }
+        }
+
+        if !states.open && !states.closed {
+            states = IssueStates::default();
+        }
+        (states, unknown)
+    }
+
+    pub fn token_env(&self) -> &str {
+        self.token_env.as_deref().unwrap_or("COMMIT_LSP_TOKEN")
+    }
+
+    /// How long the credentials stay valid, `None` if they never expire.
+    pub fn credentials_cache_duration(&self) -> Option<Duration> {
+        self.credentials_cache_seconds.map(Duration::from_secs)
+    }
+}
+
+/// How the summary of an opened commit message is shown.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OpenSummary {
+    #[default]
+    Off,
+    /// Write it to the log of the language server (`window/logMessage`).
+    Log,
+    /// Show it as a notification (`window/showMessage`).
+    Message,
+}
+
+/// The supported issue trackers.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IssueTrackerType {
+    AzureDevops,
+    Gitlab,
+    /// Not detectable from the host, so it has to be configured.
+    Redmine,
+    /// Jira Cloud, detected for `*.atlassian.net`.
+    Jira,
+}
+
+/// Which issues are listed for completion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IssueStates {
+    pub open: bool,
+    pub closed: bool,
+}
+
+impl Default for IssueStates {
+    fn default() -> Self {
+        Self {
+            open: true,
+            closed: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Repository {
     pub types: Vec<CommitElementDefinition>,
     pub scopes: Vec<CommitElementDefinition>,
+
+    /// Maximum number of characters allowed in the subject line. A limit of 0 disables the check.
+    pub subject_max_length: Option<usize>,
+
+    /// Maximum number of characters in a line of the body. Disabled by default, as the body
+    /// might contain long urls or logs.
+    pub body_max_line_length: Option<usize>,
+
+    /// Regex matching references to tickets, e.g. `PROJ-([0-9]+)` for Jira keys.
+    /// If the regex has a capture group, then the first group is used as the ticket key.
+    /// Only numeric keys can be looked up in the issue tracker.
+    /// Defaults to `#([0-9]+)`.
+    pub issue_reference_pattern: Option<String>,
+
+    /// Footer keywords (like `Closes` or `Refs`) that must introduce every ticket reference.
+    /// An empty list disables the check.
+    pub reference_keywords: Vec<String>,
+
+    /// Keywords (like `Closes` or `Fixes`) that mark the reference following them as closing the
+    /// ticket. Matched case-insensitively, defaults to the keywords of GitHub.
+    pub closing_keywords: Vec<String>,
+
+    /// Warn if the subject does not start with a verb in imperative mood.
+    pub check_imperative_mood: bool,
+
+    /// Report non-ASCII characters in the subject line.
+    pub ascii_only_subject: bool,
+
+    /// How the `!` marker and the `BREAKING CHANGE:` footer must correspond to each other.
+    pub breaking_change_check: BreakingChangeCheck,
+
+    /// Require a `Signed-off-by: Name <email>` trailer (Developer Certificate of Origin).
+    pub require_sign_off: bool,
+
+    /// Warn if none of the staged files lies within the `paths` of the declared scope.
+    pub check_scope_matches_staged: bool,
+
+    /// The least severe kind of diagnostic that makes `commit-lsp lint` fail.
+    pub fail_on: FailureThreshold,
+
+    /// Lowercase the commit type when formatting the message.
+    pub format_lowercase_type: bool,
+
+    /// Use the conventional commit types (`feat`, `fix`, ...) if no `types` are configured.
+    pub use_default_types: bool,
+
+    /// Whether `types` are only the conventional defaults. They are offered for completion, but
+    /// commit types are only checked against types configured by the repository or the user.
+    #[serde(skip)]
+    pub types_are_defaults: bool,
+
+    /// Warn about references to tickets the issue tracker does not know.
+    pub check_references_exist: bool,
+
+    /// Recognize `!123` as reference to a merge request (Gitlab).
+    pub merge_request_references: bool,
+
+    /// Lint merge commits (`Merge branch ...`) like other commits instead of ignoring them.
+    pub lint_merge_commits: bool,
+
+    /// The format of the header line.
+    pub header_style: HeaderStyle,
+
+    /// Commit types (like `feat` or `fix`) whose messages must have a body.
+    pub types_requiring_body: Vec<String>,
+
+    /// Commit types whose headers must have a scope.
+    pub types_requiring_scope: Vec<String>,
+
+    /// Severity per rule code (like `subject-too-long = "error"`), replacing the default severity
+    /// of the rule.
+    pub severities: HashMap<String, Severity>,
+
+    /// Codes of the rules (like `subject-trailing-period`) that never report anything.
+    pub disabled_rules: Vec<String>,
+
+    /// The git remote whose url determines the issue tracker (default: `origin`).
+    pub remote_name: Option<String>,
+
+    /// Url of the repository on the issue tracker, e.g. `https://gitlab.com/owner/project`.
+    /// Takes precedence over the url of the git remote.
+    pub issue_tracker_url: Option<String>,
+}
+
+#[derive(Deserialize, clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FailureThreshold {
+    /// Only fail on errors.
+    #[default]
+    Error,
+    /// Fail on errors and warnings.
+    Warning,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HeaderStyle {
+    /// `type(scope): subject`
+    #[default]
+    Conventional,
+    /// `✨ type(scope): subject` or `:sparkles: type(scope): subject`, the emoji is optional.
+    Gitmoji,
+}
+
+/// Severity of a diagnostic, for overriding the default of a rule.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+    Hint,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BreakingChangeCheck {
+    /// Both ways of marking a breaking change are independent.
+    #[default]
+    None,
+    /// A `!` in the header requires a `BREAKING CHANGE:` footer describing the change.
+    Footer,
+    /// A `BREAKING CHANGE:` footer requires a `!` in the header.
+    Marker,
+    /// Require both the `!` and the footer if either is present.
+    Both,
+}
+
+impl Default for Repository {
+    fn default() -> Self {
+        Self {
+            types: Vec::new(),
+            scopes: Vec::new(),
+            subject_max_length: Some(72),
+            body_max_line_length: None,
+            issue_reference_pattern: None,
+            reference_keywords: Vec::new(),
+            closing_keywords: [
+                "close", "closes", "closed", "fix", "fixes", "fixed", "resolve", "resolves",
+                "resolved",
+            ]
+            .map(ToOwned::to_owned)
+            .to_vec(),
+            check_imperative_mood: false,
+            ascii_only_subject: false,
+            breaking_change_check: BreakingChangeCheck::None,
+            require_sign_off: false,
+            check_scope_matches_staged: false,
+            fail_on: FailureThreshold::Error,
+            format_lowercase_type: false,
+            use_default_types: true,
+            types_are_defaults: false,
+            check_references_exist: false,
+            merge_request_references: false,
+            lint_merge_commits: false,
+            header_style: HeaderStyle::Conventional,
+            types_requiring_body: Vec::new(),
+            types_requiring_scope: Vec::new(),
+            severities: HashMap::new(),
+            disabled_rules: Vec::new(),
+            remote_name: None,
+            issue_tracker_url: None,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Clone, Default)]
@@ -31,71 +353,429 @@ pub struct CommitElementDefinition {
     pub name: String,
     pub summary: String,
     pub description: String,
+
+    /// Paths (relative to the repository root) belonging to this element.
+    /// Scopes whose paths contain staged files are suggested first.
+    #[serde(default)]
+    pub paths: Vec<PathBuf>,
+}
+
+impl CommitElementDefinition {
+    fn builtin(name: &str, summary: &str, description: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            summary: summary.to_owned(),
+            description: description.to_owned(),
+            paths: Vec::new(),
+        }
+    }
+}
+
+impl Repository {
+    /// Scopes that are always accepted, in addition to the configured ones.
+    ///
+    /// These are used by dependency update bots like dependabot or renovate.
+    pub fn builtin_scopes() -> Vec<CommitElementDefinition> {
+        vec![
+            CommitElementDefinition::builtin(
+                "deps",
+                "Dependencies",
+                "Update of dependencies used at runtime.",
+            ),
+            CommitElementDefinition::builtin(
+                "deps-dev",
+                "Development dependencies",
+                "Update of dependencies only used during development.",
+            ),
+        ]
+    }
 }
 
 impl User {
-    pub fn load_default_file(health: &mut HealthReport) -> Self {
-        health.set_context("User Configuration");
+    const DEFAULT_FETCH_COOLDOWN: Duration = Duration::from_millis(300);
+    const DEFAULT_DIAGNOSTICS_COOLDOWN: Duration = Duration::from_millis(150);
 
-        let proj_dir = ProjectDirs::from("at", "texel", "commit-lsp").unwrap();
-        let dir = proj_dir.config_dir();
+    /// Quiet period after document changes before tickets get requested from upstream.
+    pub fn fetch_cooldown(&self) -> Duration {
+        self.fetch_cooldown_ms
+            .map(Duration::from_millis)
+            .unwrap_or(Self::DEFAULT_FETCH_COOLDOWN)
+    }
 
-        let config_path = dir.join("config.toml");
+    /// Quiet period after document changes before the diagnostics are published.
+    pub fn diagnostics_cooldown(&self) -> Duration {
+        self.diagnostics_cooldown_ms
+            .map(Duration::from_millis)
+            .unwrap_or(Self::DEFAULT_DIAGNOSTICS_COOLDOWN)
+    }
 
-        let check = health.start(format!("open config file ('{}')", config_path.display()));
-        if !config_path.exists() {
-            info!("Using default config");
-            check.info("File does not exist, using default config.");
-            return Default::default();
-        }
-        let text = (|| {
-            let mut config_file = File::open(&config_path)?;
+    /// Commented example configuration, used by `commit-lsp init`.
+    pub const EXAMPLE: &'static str = include_str!("templates/config.toml");
 
-            let mut text = String::new();
-            config_file.read_to_string(&mut text)?;
+    /// Location of the user configuration file.
+    pub fn default_path() -> PathBuf {
+        let proj_dir = ProjectDirs::from("at", "texel", "commit-lsp").unwrap();
+        proj_dir.config_dir().join("config.toml")
+    }
 
-            std::io::Result::Ok(text)
-        })()
-        .finish_check(check)
-        .unwrap();
+    pub fn ticket_inlay_hints(&self) -> bool {
+        self.ticket_inlay_hints.unwrap_or(true)
+    }
+
+    pub fn load_default_file(health: &mut HealthReport) -> Self {
+        health.set_context("User Configuration");
+
+        Self::load_file(&Self::default_path(), health)
+    }
 
-        info!("Loading config file '{path}'", path = config_path.display());
-        toml::from_str(&text)
-            .report(health, "parse config")
-            .expect("Failed to parse config!")
+    fn load_file(config_path: &Path, health: &mut HealthReport) -> Self {
+        read_config_file(config_path, health).unwrap_or_default()
     }
 }
 
 impl Repository {
+    /// Commented example configuration, used by `commit-lsp init`.
+    pub const EXAMPLE: &'static str = include_str!("templates/commit-lsp.toml");
+
+    /// Names of the configuration file relative to the root of the repository,
+    /// the first one that exists is used.
+    pub const FILE_NAMES: [&'static str; 2] = [".commit-lsp.toml", ".config/commit-lsp.toml"];
+
+    /// Environment variable with the path of the configuration file, takes precedence over
+    /// [`Self::FILE_NAMES`].
+    pub const PATH_ENV: &'static str = "COMMIT_LSP_CONFIG";
+
     pub fn load_default_file(health: &mut HealthReport) -> Self {
         health.set_context("Repository Configuration");
 
-        let root_folder = get_repo_root()
-            .report(health, "is inside git repository")
-            .unwrap();
+        if let Some(path) = std::env::var_os(Self::PATH_ENV) {
+            return Self::load_file(Path::new(&path), health);
+        }
+
+        let Some(root_folder) = get_repo_root().report(health, "is inside git repository") else {
+            warn!("Not inside a git repository, using default config");
+            return Self::default().with_default_types();
+        };
 
-        let config_path = root_folder.join(".commit-lsp.toml");
+        Self::load_file(&Self::find_file(&root_folder), health)
+    }
+
+    /// The first of [`Self::FILE_NAMES`] that exists in `root_folder`, the first name if none does.
+    fn find_file(root_folder: &Path) -> PathBuf {
+        Self::FILE_NAMES
+            .iter()
+            .map(|name| root_folder.join(name))
+            .find(|path| path.exists())
+            .unwrap_or_else(|| root_folder.join(Self::FILE_NAMES[0]))
+    }
+
+    /// Load the configuration from `config_path` instead of the root of the repository.
+    pub fn load_from_path(config_path: &Path, health: &mut HealthReport) -> Self {
+        health.set_context("Repository Configuration");
+
+        Self::load_file(config_path, health)
+    }
+
+    fn load_file(config_path: &Path, health: &mut HealthReport) -> Self {
+        read_config_file::<Self>(config_path, health)
+            .unwrap_or_default()
+            .with_default_types()
+    }
+
+    /// Add the personal types and scopes of the user configuration.
+    ///
+    /// Definitions of the repository take precedence, user definitions with the same name as a
+    /// repository definition are ignored.
+    pub fn with_user_definitions(mut self, user: &User) -> Self {
+        fn merge(ours: &mut Vec<CommitElementDefinition>, theirs: &[CommitElementDefinition]) {
+            let new: Vec<_> = theirs
+                .iter()
+                .filter(|t| !ours.iter().any(|o| o.name == t.name))
+                .cloned()
+                .collect();
+            ours.extend(new);
+        }
+
+        self.types_are_defaults &= user.types.is_empty();
+        merge(&mut self.types, &user.types);
+        merge(&mut self.scopes, &user.scopes);
+        self
+    }
+
+    pub fn remote_name(&self) -> &str {
+        self.remote_name.as_deref().unwrap_or("origin")
+    }
 
-        let check = health.start(format!("open config file ('{}')", config_path.display()));
+    /// Read the configuration again, e.g. after it was edited.
+    ///
+    /// Reads `config_path` if given, otherwise the configuration of the current repository.
+    /// Unlike [`Self::load_default_file`], problems are returned instead of aborting.
+    pub fn reload(config_path: Option<&Path>) -> Result<Self, String> {
+        let config_path = match (config_path, std::env::var_os(Self::PATH_ENV)) {
+            (Some(path), _) => path.to_owned(),
+            (None, Some(path)) => PathBuf::from(path),
+            (None, None) => Self::find_file(&get_repo_root().ok_or("Not inside a git repository")?),
+        };
         if !config_path.exists() {
-            info!("Using default config");
-            check.info("File does not exist, using default config.");
-            return Default::default();
+            return Ok(Self::default().with_default_types());
         }
-        let text = (|| {
-            let mut config_file = File::open(&config_path)?;
 
-            let mut text = String::new();
-            config_file.read_to_string(&mut text)?;
+        info!(
+            "Reloading config file '{path}'",
+            path = config_path.display()
+        );
+        let text = std::fs::read_to_string(&config_path)
+            .map_err(|e| format!("Failed to read '{}': {e}", config_path.display()))?;
+        let config: Self = toml::from_str(&text)
+            .map_err(|e| format!("Failed to parse '{}': {e}", config_path.display()))?;
+        Ok(config.with_default_types())
+    }
+
+    /// Use the conventional commit types, if no types are configured and the user did not opt
+    /// out via `use_default_types`.
+    fn with_default_types(mut self) -> Self {
+        if self.types.is_empty() && self.use_default_types {
+            self.types = Self::default_types();
+            self.types_are_defaults = true;
+        }
+        self
+    }
+
+    /// The types recommended by the conventional commits specification.
+    pub fn default_types() -> Vec<CommitElementDefinition> {
+        [
+            ("feat", "A new feature", "Adds new user facing functionality."),
+            ("fix", "A bugfix", "Resolves a bug in existing code."),
+            ("docs", "Documentation only changes", "Extends or updates documentation."),
+            (
+                "style",
+                "Formatting changes",
+                "Changes that do not affect the meaning of the code (white-space, formatting, etc).",
+            ),
+            (
+                "refactor",
+                "Internal code changes",
+                "Code changes that neither fix a bug nor add a feature.",
+            ),
+            ("perf", "Performance improvements", "Code changes that improve performance."),
+            ("test", "New or fixed tests", "Adds missing tests or corrects existing tests."),
+            (
+                "build",
+                "Build system or dependency changes",
+                "Changes that affect the build system or external dependencies.",
+            ),
+            ("ci", "CI configuration changes", "Changes to the CI configuration files and scripts."),
+            (
+                "chore",
+                "General housekeeping",
+                "Other changes that don't modify source or test files.",
+            ),
+            ("revert", "Reverts a previous commit", "Undoes the changes of an earlier commit."),
+        ]
+        .into_iter()
+        .map(|(name, summary, description)| {
+            CommitElementDefinition::builtin(name, summary, description)
+        })
+        .collect()
+    }
+}
+
+/// Read and parse the config file at `config_path`, reporting problems to `health`.
+///
+/// Returns `None` if the file does not exist or is invalid, so that the defaults can be used.
+fn read_config_file<T: DeserializeOwned>(
+    config_path: &Path,
+    health: &mut HealthReport,
+) -> Option<T> {
+    let check = health.start(format!("open config file ('{}')", config_path.display()));
+    if !config_path.exists() {
+        info!("Using default config");
+        check.info("File does not exist, using default config.");
+        return None;
+    }
+    let text = (|| {
+        let mut config_file = File::open(config_path)?;
+
+        let mut text = String::new();
+        config_file.read_to_string(&mut text)?;
+
+        std::io::Result::Ok(text)
+    })()
+    .finish_check(check);
+    let text = match text {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("Failed to read config file, using default config: {e}");
+            return None;
+        }
+    };
+
+    info!("Loading config file '{path}'", path = config_path.display());
+    match toml::from_str(&text).report(health, "parse config") {
+        Ok(config) => Some(config),
+        Err(e) => {
+            warn!("Failed to parse config file, using default config: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_types() {
+        let config: Repository = toml::from_str("").unwrap();
+        let types: Vec<_> = config
+            .with_default_types()
+            .types
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        assert_eq!(types.len(), 11);
+        assert_eq!(types.first().map(String::as_str), Some("feat"));
+        assert!(
+            Repository::default()
+                .with_default_types()
+                .types_are_defaults
+        );
+
+        let config: Repository = toml::from_str("use_default_types = false").unwrap();
+        assert!(config.with_default_types().types.is_empty());
+
+        let config: Repository =
+            toml::from_str("[[types]]\nname = 'x'\nsummary = ''\ndescription = ''").unwrap();
+        let config = config.with_default_types();
+        assert_eq!(config.types.len(), 1);
+        assert!(!config.types_are_defaults);
+    }
 
-            std::io::Result::Ok(text)
-        })()
-        .finish_check(check)
+    #[test]
+    fn invalid_file_uses_defaults() {
+        let path = std::env::temp_dir().join(format!("commit-lsp-{}.toml", std::process::id()));
+        std::fs::write(&path, "types = [\n[[remotes]\nhost =").unwrap();
+
+        let repo = Repository::load_file(&path, &mut HealthReport::silent());
+        let user = User::load_file(&path, &mut HealthReport::silent());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(repo.types.len(), Repository::default_types().len());
+        assert_eq!(repo.subject_max_length, Some(72));
+        assert!(user.remotes.is_empty());
+    }
+
+    #[test]
+    fn load_from_path() {
+        let path =
+            std::env::temp_dir().join(format!("commit-lsp-path-{}.toml", std::process::id()));
+        std::fs::write(&path, "subject_max_length = 50").unwrap();
+
+        let repo = Repository::load_from_path(&path, &mut HealthReport::silent());
+        let reloaded = Repository::reload(Some(&path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(repo.subject_max_length, Some(50));
+        assert_eq!(reloaded.subject_max_length, Some(50));
+        assert_eq!(repo.types.len(), Repository::default_types().len());
+    }
+
+    #[test]
+    fn alternative_file_name() {
+        let root = std::env::temp_dir().join(format!("commit-lsp-root-{}", std::process::id()));
+        std::fs::create_dir_all(root.join(".config")).unwrap();
+
+        let default = Repository::find_file(&root);
+        std::fs::write(root.join(".config/commit-lsp.toml"), "").unwrap();
+        let alternative = Repository::find_file(&root);
+        std::fs::write(root.join(".commit-lsp.toml"), "").unwrap();
+        let both = Repository::find_file(&root);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(default, root.join(".commit-lsp.toml"));
+        assert_eq!(alternative, root.join(".config/commit-lsp.toml"));
+        assert_eq!(both, root.join(".commit-lsp.toml"));
+    }
+
+    #[test]
+    fn user_definitions() {
+        // only personal definitions, without any remotes
+        let user: User = toml::from_str(
+            "[[types]]\nname = 'feat'\nsummary = 'mine'\ndescription = ''\n\
+             [[types]]\nname = 'wip'\nsummary = 'mine'\ndescription = ''\n\
+             [[scopes]]\nname = 'scratch'\nsummary = 'mine'\ndescription = ''",
+        )
+        .unwrap();
+        assert!(user.remotes.is_empty());
+        let repo: Repository = toml::from_str(
+            "[[types]]\nname = 'feat'\nsummary = 'repo'\ndescription = ''\n\
+             [[scopes]]\nname = 'ui'\nsummary = 'repo'\ndescription = ''",
+        )
         .unwrap();
 
-        info!("Loading config file '{path}'", path = config_path.display());
-        toml::from_str(&text)
-            .report(health, "parse config")
-            .expect("Failed to parse config!")
+        let defaults = Repository::default().with_default_types();
+        assert!(!defaults.with_user_definitions(&user).types_are_defaults);
+
+        let merged = repo.with_user_definitions(&user);
+        let names = |defs: &[CommitElementDefinition]| -> Vec<(String, String)> {
+            defs.iter()
+                .map(|d| (d.name.clone(), d.summary.clone()))
+                .collect()
+        };
+        assert_eq!(
+            names(&merged.types),
+            [
+                ("feat".into(), "repo".into()),
+                ("wip".into(), "mine".into())
+            ]
+        );
+        assert_eq!(
+            names(&merged.scopes),
+            [
+                ("ui".into(), "repo".into()),
+                ("scratch".into(), "mine".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn issue_states() {
+        let remote = |states: &str| -> Remote {
+            toml::from_str(&format!("host = 'x'\nissue_states = {states}")).unwrap()
+        };
+
+        let all = IssueStates {
+            open: true,
+            closed: true,
+        };
+        assert_eq!(
+            remote("[]").issue_states(),
+            (IssueStates::default(), vec![])
+        );
+        assert_eq!(remote("['open', 'closed']").issue_states(), (all, vec![]));
+        assert_eq!(
+            remote("['closed', 'merged']").issue_states(),
+            (
+                IssueStates {
+                    open: false,
+                    closed: true
+                },
+                vec!["merged"]
+            )
+        );
+        assert_eq!(
+            remote("['opened']").issue_states(),
+            (IssueStates::default(), vec!["opened"])
+        );
+    }
+
+    #[test]
+    fn examples_are_valid() {
+        let repo: Repository = toml::from_str(Repository::EXAMPLE).unwrap();
+        let types: Vec<_> = repo.types.iter().map(|t| t.name.as_str()).collect();
+        assert!(types.starts_with(&["feat", "fix", "docs"]));
+
+        let user: User = toml::from_str(User::EXAMPLE).unwrap();
+        assert_eq!(user.remotes.len(), 1);
     }
 }
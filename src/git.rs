@@ -1,10 +1,19 @@
 use git_url_parse::GitUrl;
-use std::{path::PathBuf, process::Command};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
-/// Get the url of the `origin` remote.
-pub fn guess_repo_url() -> Option<GitUrl> {
+/// Get the url of the remote with the given name, e.g. `origin`.
+pub fn guess_repo_url(remote_name: &str) -> Option<GitUrl> {
+    remote_url(Path::new("."), remote_name)
+}
+
+fn remote_url(repo: &Path, remote_name: &str) -> Option<GitUrl> {
     let cmd = Command::new("git")
-        .args(["ls-remote", "--get-url", "origin"])
+        .args(["ls-remote", "--get-url", remote_name])
+        .current_dir(repo)
         .output()
         .unwrap();
 
@@ -31,3 +40,77 @@ pub fn get_repo_root() -> Option<PathBuf> {
 
     Some(PathBuf::from(path.trim()))
 }
+
+/// Get the paths of all files staged for commit, relative to the repository root.
+pub fn get_staged_files() -> Vec<PathBuf> {
+    let cmd = Command::new("git")
+        .args(["diff", "--cached", "--name-only"])
+        .output()
+        .unwrap();
+
+    if !cmd.status.success() {
+        return Vec::new();
+    }
+
+    String::from_utf8_lossy(&cmd.stdout)
+        .lines()
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Load the contents of the commit message template configured via `commit.template`.
+pub fn get_commit_template() -> Option<String> {
+    let cmd = Command::new("git")
+        .args(["config", "--path", "commit.template"])
+        .output()
+        .unwrap();
+
+    if !cmd.status.success() {
+        return None;
+    }
+
+    let path = String::from_utf8(cmd.stdout).unwrap();
+    let path = PathBuf::from(path.trim());
+
+    // relative template paths are interpreted relative to the repository root
+    let path = match get_repo_root() {
+        Some(root) if path.is_relative() => root.join(path),
+        _ => path,
+    };
+
+    fs::read_to_string(path).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn select_remote() {
+        let repo = std::env::temp_dir().join(format!("commit-lsp-remotes-{}", std::process::id()));
+        fs::create_dir_all(&repo).unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .args(args)
+                .current_dir(&repo)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        git(&["init", "--quiet"]);
+        git(&["remote", "add", "origin", "git@gitlab.com:me/project.git"]);
+        git(&[
+            "remote",
+            "add",
+            "upstream",
+            "git@gitlab.com:team/project.git",
+        ]);
+
+        let origin = remote_url(&repo, "origin");
+        let upstream = remote_url(&repo, "upstream");
+        fs::remove_dir_all(&repo).unwrap();
+
+        assert_eq!(origin.and_then(|u| u.owner).as_deref(), Some("me"));
+        assert_eq!(upstream.and_then(|u| u.owner).as_deref(), Some("team"));
+    }
+}
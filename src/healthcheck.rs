@@ -7,6 +7,7 @@ use colored::Colorize as _;
 pub struct HealthReport {
     context: String,
     is_silent: bool,
+    errors: usize,
 }
 
 impl HealthReport {
@@ -14,6 +15,7 @@ impl HealthReport {
         Self {
             context: ctx.into(),
             is_silent: false,
+            errors: 0,
         }
     }
 
@@ -21,9 +23,11 @@ impl HealthReport {
         Self {
             context: String::new(),
             is_silent: true,
+            errors: 0,
         }
     }
 
+    #[allow(clippy::print_stdout)] // silent reports, as used by the language server, return early
     pub fn set_context(&mut self, ctx: impl Into<String>) {
         self.context = ctx.into();
 
@@ -38,7 +42,16 @@ impl HealthReport {
         println!("{} {} {}", padding, self.context.bold(), padding);
     }
 
+    /// Whether any of the checks reported an error, also for silent reports.
+    pub fn has_errors(&self) -> bool {
+        self.errors > 0
+    }
+
+    #[allow(clippy::print_stdout)] // silent reports, as used by the language server, return early
     pub fn report(&mut self, name: impl Into<String>, state: ComponentState) {
+        if matches!(state, ComponentState::Error(_)) {
+            self.errors += 1;
+        }
         if self.is_silent {
             return;
         }
@@ -137,3 +150,19 @@ impl<'a> OngoingReport<'a> {
             .report(self.component, ComponentState::Error(txt.into()));
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn silent_report_counts_errors() {
+        let mut health = HealthReport::silent();
+        health.start("a").ok();
+        health.start("b").warn("careful");
+        assert!(!health.has_errors());
+
+        health.start("c").error("broken");
+        assert!(health.has_errors());
+    }
+}
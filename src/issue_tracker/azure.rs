@@ -1,62 +1,113 @@
 use async_trait::async_trait;
-use reqwest::Method;
+use chrono::DateTime;
+use reqwest::{Method, RequestBuilder, Response};
 use secure_string::SecureString;
 use serde::Serialize;
 
-use super::{IssueTrackerAdapter, Ticket, UpstreamError};
+use super::{
+    retry::{self, RetryPolicy},
+    HttpClient, IssueTrackerAdapter, Ticket, TicketStatus, UpstreamError,
+};
+use crate::config::IssueStates;
+use crate::text_util::html_to_markdown;
 
 pub struct AzureDevops {
     pat: SecureString,
-    organization: String,
-    project: String,
-    client: reqwest::Client,
+    base_url: String,
+    client: HttpClient,
+    retry: RetryPolicy,
+    states: IssueStates,
 }
 
 impl AzureDevops {
-    pub fn new(pat: SecureString, organization: String, project: String) -> Self {
+    pub fn new(
+        client: HttpClient,
+        pat: SecureString,
+        organization: String,
+        project: String,
+    ) -> Self {
+        Self::with_base_url(
+            client,
+            pat,
+            format!("https://dev.azure.com/{organization}/{project}/_apis"),
+        )
+    }
+
+    fn with_base_url(client: HttpClient, pat: SecureString, base_url: String) -> Self {
         Self {
-            client: reqwest::Client::new(),
+            client,
             pat,
-            organization,
-            project,
+            base_url,
+            retry: RetryPolicy::default(),
+            states: IssueStates::default(),
         }
     }
 
-    fn base_url(&self) -> String {
-        format!(
-            "https://dev.azure.com/{}/{}/_apis",
-            self.organization, self.project
-        )
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_issue_states(mut self, states: IssueStates) -> Self {
+        self.states = states;
+        self
+    }
+
+    /// Send the request, retrying it on transient errors.
+    async fn send(&self, request: RequestBuilder) -> Result<Response, UpstreamError> {
+        let request = &request;
+        let response = self
+            .retry
+            .run(
+                move || async move {
+                    request
+                        .try_clone()
+                        .expect("requests have no streaming body")
+                        .send()
+                        .await?
+                        .error_for_status()
+                },
+                retry::is_transient,
+            )
+            .await
+            .map_err(|e| self.client.convert(e))?;
+
+        Ok(response)
+    }
+
+    fn base_url(&self) -> &str {
+        &self.base_url
     }
 }
 
 #[async_trait]
 impl IssueTrackerAdapter for AzureDevops {
     async fn list_ticket_numbers(&self) -> Result<Vec<u64>, UpstreamError> {
-        let query = "SELECT [System.Id] FROM WorkItems WHERE [System.TeamProject] = @project AND [Assigned To] = @me AND [System.Id] in (@MyRecentActivity)".to_owned();
-        let result = self
+        let query = format!(
+            "SELECT [System.Id] FROM WorkItems WHERE [System.TeamProject] = @project AND [Assigned To] = @me AND [System.Id] in (@MyRecentActivity){}",
+            state_condition(self.states)
+        );
+        let request = self
             .client
             .request(Method::POST, format!("{}/wit/wiql", self.base_url()))
             .query(&[("api-version", "7.0")])
             .json(&QueryRequest { query })
-            .basic_auth("", Some(self.pat.unsecure()))
-            .send()
-            .await;
+            .basic_auth("", Some(self.pat.unsecure()));
 
-        let response: serde_json::Value = result.unwrap().json().await.unwrap();
+        let response: serde_json::Value = self.send(request).await?.json().await?;
 
-        let items: Vec<_> = response["workItems"]
+        let items = response["workItems"]
             .as_array()
-            .unwrap()
+            .ok_or_else(|| missing_field("workItems"))?
             .iter()
-            .map(|i| i["id"].as_u64().unwrap())
-            .collect();
+            .map(|i| i["id"].as_u64().ok_or_else(|| missing_field("id")))
+            .collect::<Result<_, _>>()?;
 
         Ok(items)
     }
 
     async fn get_ticket_details(&self, ids: &[u64]) -> Result<Vec<Ticket>, UpstreamError> {
-        let result = self
+        let request = self
             .client
             .request(
                 Method::POST,
@@ -64,34 +115,86 @@ impl IssueTrackerAdapter for AzureDevops {
             )
             .json(&WorkItemsBatchRequest {
                 ids,
-                fields: &["System.Title", "System.Description"],
+                fields: &[
+                    "System.Title",
+                    "System.Description",
+                    "System.State",
+                    "System.ChangedDate",
+                ],
             })
             .query(&[("api-version", "7.0")])
-            .basic_auth("", Some(self.pat.unsecure()))
-            .send()
-            .await;
+            .basic_auth("", Some(self.pat.unsecure()));
 
-        let response: serde_json::Value = result.unwrap().json().await.unwrap();
-        let items: Vec<_> = response["value"]
+        let response: serde_json::Value = self.send(request).await?.json().await?;
+        let items = response["value"]
             .as_array()
-            .unwrap()
+            .ok_or_else(|| missing_field("value"))?
             .iter()
             .map(|i| {
-                Ticket::new(
-                    i["id"].as_u64().unwrap(),
-                    i["fields"]["System.Title"].as_str().unwrap().to_owned(),
-                    i["fields"]["System.Description"]
-                        .as_str()
-                        // We need to handle the case where a work item has no description,
-                        // so we just default to empty string.
-                        .unwrap_or_default()
-                        .to_owned(),
+                Ok::<_, UpstreamError>(
+                    Ticket::new(
+                        i["id"].as_u64().ok_or_else(|| missing_field("id"))?,
+                        i["fields"]["System.Title"]
+                            .as_str()
+                            .ok_or_else(|| missing_field("System.Title"))?
+                            .to_owned(),
+                        // The description is rich text (HTML), but it is shown as Markdown.
+                        html_to_markdown(
+                            i["fields"]["System.Description"]
+                                .as_str()
+                                // We need to handle the case where a work item has no description,
+                                // so we just default to empty string.
+                                .unwrap_or_default(),
+                        ),
+                    )
+                    .with_status(
+                        i["fields"]["System.State"]
+                            .as_str()
+                            .map_or(TicketStatus::Unknown, work_item_status),
+                    )
+                    .with_updated_at(
+                        i["fields"]["System.ChangedDate"]
+                            .as_str()
+                            .and_then(|d| DateTime::parse_from_rfc3339(d).ok())
+                            .map(|d| d.to_utc()),
+                    ),
                 )
             })
-            .collect();
+            .collect::<Result<_, _>>()?;
 
         Ok(items)
     }
+
+    fn web_url(&self, id: u64) -> Option<String> {
+        let project_url = self.base_url().strip_suffix("/_apis")?;
+        Some(format!("{project_url}/_workitems/edit/{id}"))
+    }
+}
+
+/// The states of the default processes that `work_item_status` maps to `Closed`.
+const CLOSED_STATES: &str = "('Resolved', 'Closed', 'Done', 'Removed')";
+
+/// WIQL condition restricting the work items to the given states.
+fn state_condition(states: IssueStates) -> String {
+    match (states.open, states.closed) {
+        (true, false) => format!(" AND [System.State] NOT IN {CLOSED_STATES}"),
+        (false, true) => format!(" AND [System.State] IN {CLOSED_STATES}"),
+        _ => String::new(),
+    }
+}
+
+/// Map the default work item states of the Agile, Scrum, Basic and CMMI processes.
+fn work_item_status(state: &str) -> TicketStatus {
+    match state {
+        "New" | "To Do" | "Proposed" | "Approved" => TicketStatus::Open,
+        "Active" | "Committed" | "Doing" | "In Progress" => TicketStatus::InProgress,
+        "Resolved" | "Closed" | "Done" | "Removed" => TicketStatus::Closed,
+        _ => TicketStatus::Unknown,
+    }
+}
+
+fn missing_field(name: &str) -> UpstreamError {
+    UpstreamError::InvalidResponse(format!("Missing field '{name}'"))
 }
 
 #[derive(Serialize)]
@@ -104,3 +207,226 @@ struct WorkItemsBatchRequest<'a> {
     pub ids: &'a [u64],
     pub fields: &'a [&'static str],
 }
+
+#[cfg(all(test, feature = "http-fixtures"))]
+mod test {
+    use std::time::Duration;
+
+    use wiremock::{
+        matchers::{body_string_contains, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::issue_tracker::{fixtures::json_response, Timeouts, USER_AGENT};
+
+    fn adapter(server: &MockServer) -> AzureDevops {
+        adapter_with_client(server, HttpClient::default())
+    }
+
+    fn adapter_with_client(server: &MockServer, client: HttpClient) -> AzureDevops {
+        AzureDevops::with_base_url(
+            client,
+            "secret".into(),
+            format!("{}/organization/project/_apis", server.uri()),
+        )
+        .with_retry(RetryPolicy {
+            retries: 2,
+            base_delay: Duration::from_millis(1),
+        })
+    }
+
+    #[tokio::test]
+    async fn list_ticket_numbers() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/organization/project/_apis/wit/wiql"))
+            .respond_with(json_response(200, "azure/wiql.json"))
+            .mount(&server)
+            .await;
+
+        let ids = adapter(&server).list_ticket_numbers().await.unwrap();
+
+        assert_eq!(ids, [42, 1337]);
+    }
+
+    #[tokio::test]
+    async fn get_ticket_details() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/organization/project/_apis/wit/workitemsbatch"))
+            .respond_with(json_response(200, "azure/workitemsbatch.json"))
+            .mount(&server)
+            .await;
+
+        let tickets = adapter(&server)
+            .get_ticket_details(&[42, 1337])
+            .await
+            .unwrap();
+
+        assert_eq!(tickets.len(), 2);
+        assert_eq!(tickets[0].id(), 42);
+        assert_eq!(tickets[0].title(), "Implement work item completion");
+        assert_eq!(tickets[0].text(), "Complete work items in commit messages.");
+        assert_eq!(
+            tickets[0].updated_at().unwrap().to_rfc3339(),
+            "2024-05-20T18:30:12.540+00:00"
+        );
+        assert_eq!(tickets[0].status(), TicketStatus::InProgress);
+        assert_eq!(tickets[1].id(), 1337);
+        assert_eq!(tickets[1].text(), "");
+        assert_eq!(tickets[1].status(), TicketStatus::Unknown);
+    }
+
+    #[tokio::test]
+    async fn html_description_is_converted() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/organization/project/_apis/wit/workitemsbatch"))
+            .respond_with(json_response(200, "azure/workitemsbatch_html.json"))
+            .mount(&server)
+            .await;
+
+        let tickets = adapter(&server).get_ticket_details(&[7]).await.unwrap();
+
+        assert_eq!(tickets[0].title(), "Render <div> & friends");
+        assert_eq!(
+            tickets[0].text(),
+            "Hover shows raw HTML.\n\nSteps:\nopen a commit\n\n- hover #7\n- see <div> tags"
+        );
+    }
+
+    #[tokio::test]
+    async fn slow_server_times_out() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/organization/project/_apis/wit/wiql"))
+            .respond_with(json_response(200, "azure/wiql.json").set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+
+        let client = HttpClient::new(Timeouts {
+            connect: Duration::from_millis(50),
+            request: Duration::from_millis(50),
+        });
+        let error = adapter_with_client(&server, client)
+            .list_ticket_numbers()
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, UpstreamError::Timeout(_)), "{error}");
+    }
+
+    #[tokio::test]
+    async fn sends_user_agent() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/organization/project/_apis/wit/wiql"))
+            .and(header("user-agent", USER_AGENT))
+            .respond_with(json_response(200, "azure/wiql.json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/organization/project/_apis/wit/workitemsbatch"))
+            .and(header("user-agent", USER_AGENT))
+            .respond_with(json_response(200, "azure/workitemsbatch.json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let adapter = adapter(&server);
+        adapter.list_ticket_numbers().await.unwrap();
+        adapter.get_ticket_details(&[42, 1337]).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/organization/project/_apis/wit/wiql"))
+            .respond_with(json_response(200, "azure/wiql.json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let ids = adapter(&server).list_ticket_numbers().await.unwrap();
+
+        assert_eq!(ids, [42, 1337]);
+    }
+
+    #[tokio::test]
+    async fn unauthorized() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let result = adapter(&server).list_ticket_numbers().await;
+
+        assert!(matches!(result, Err(UpstreamError::Request(_))));
+    }
+
+    #[tokio::test]
+    async fn not_found() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(json_response(404, "azure/not_found.json"))
+            .mount(&server)
+            .await;
+
+        let result = adapter(&server).get_ticket_details(&[1]).await;
+
+        assert!(matches!(result, Err(UpstreamError::Request(_))));
+    }
+
+    #[tokio::test]
+    async fn list_open_work_items() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/organization/project/_apis/wit/wiql"))
+            .and(body_string_contains(
+                "[System.State] NOT IN ('Resolved', 'Closed', 'Done', 'Removed')",
+            ))
+            .respond_with(json_response(200, "azure/wiql.json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let ids = adapter(&server).list_ticket_numbers().await.unwrap();
+
+        assert_eq!(ids, [42, 1337]);
+    }
+
+    #[test]
+    fn state_conditions() {
+        let states = |open, closed| state_condition(IssueStates { open, closed });
+
+        assert!(states(true, false).contains("NOT IN"));
+        assert!(states(false, true).starts_with(" AND [System.State] IN"));
+        assert_eq!(states(true, true), "");
+    }
+
+    #[test]
+    fn web_url() {
+        let adapter = AzureDevops::new(
+            HttpClient::default(),
+            "secret".into(),
+            "org".to_owned(),
+            "project".to_owned(),
+        );
+
+        assert_eq!(
+            adapter.web_url(42).as_deref(),
+            Some("https://dev.azure.com/org/project/_workitems/edit/42")
+        );
+    }
+}
@@ -1,16 +1,67 @@
-use std::{fs::File, io::Read as _, path::PathBuf};
+use std::{
+    fs::File,
+    io::Read as _,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
 
 use async_trait::async_trait;
 
-use super::{IssueTrackerAdapter, Ticket, UpstreamError};
+use super::{IssueTrackerAdapter, Ticket, TicketStatus, UpstreamError};
 
 pub struct DemoAdapter {
     source_folder: PathBuf,
+    latency: Duration,
+    error_rate: f64,
+    requests: AtomicU64,
 }
 
 impl DemoAdapter {
     pub fn new(source_folder: PathBuf) -> Self {
-        Self { source_folder }
+        Self {
+            source_folder,
+            latency: Duration::ZERO,
+            error_rate: 0.0,
+            requests: AtomicU64::new(0),
+        }
+    }
+
+    /// Configure the simulated network conditions from the environment variables
+    /// `COMMIT_LSP_DEMO_LATENCY_MS` and `COMMIT_LSP_DEMO_ERROR_RATE` (between 0 and 1).
+    pub fn with_env_conditions(self) -> Self {
+        let latency = std::env::var("COMMIT_LSP_DEMO_LATENCY_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_millis)
+            .unwrap_or_default();
+        let error_rate = std::env::var("COMMIT_LSP_DEMO_ERROR_RATE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_default();
+        self.with_conditions(latency, error_rate)
+    }
+
+    pub fn with_conditions(mut self, latency: Duration, error_rate: f64) -> Self {
+        self.latency = latency;
+        self.error_rate = error_rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Wait for the simulated latency, then decide whether the request fails.
+    ///
+    /// Failures are spread evenly instead of randomly, so that a given error rate always produces
+    /// the same sequence of results. E.g. a rate of `0.5` fails every second request.
+    async fn simulate_request(&self) -> Result<(), UpstreamError> {
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+
+        let n = self.requests.fetch_add(1, Ordering::SeqCst) as f64;
+        if ((n + 1.0) * self.error_rate).floor() > (n * self.error_rate).floor() {
+            return Err(UpstreamError::Request("Simulated demo failure".into()));
+        }
+        Ok(())
     }
 
     fn load_ticket(&self, id: u64) -> Option<Ticket> {
@@ -19,36 +70,94 @@ impl DemoAdapter {
         let mut file = File::open(file).ok()?;
         let mut buffer = String::new();
         file.read_to_string(&mut buffer).ok()?;
+        let modified = file.metadata().and_then(|m| m.modified()).ok();
 
         let mut lines = buffer.lines();
         let title = lines.next()?;
-        let content = lines.skip(1).collect::<Vec<_>>().join("\n");
+        // the line after the title may contain the status
+        let status = parse_status(lines.next().unwrap_or_default());
+        let content = lines.collect::<Vec<_>>().join("\n");
 
-        Some(Ticket::new(id, title.to_owned(), content))
+        Some(
+            Ticket::new(id, title.to_owned(), content)
+                .with_status(status)
+                .with_updated_at(modified.map(Into::into)),
+        )
+    }
+}
+
+fn parse_status(line: &str) -> TicketStatus {
+    match line.trim().to_lowercase().as_str() {
+        "open" => TicketStatus::Open,
+        "in progress" => TicketStatus::InProgress,
+        "closed" => TicketStatus::Closed,
+        _ => TicketStatus::Unknown,
     }
 }
 
 #[async_trait]
 impl IssueTrackerAdapter for DemoAdapter {
     async fn list_ticket_numbers(&self) -> Result<Vec<u64>, UpstreamError> {
+        self.simulate_request().await?;
+
         let mut ids = Vec::new();
 
         for entry in self.source_folder.read_dir().expect("To open dir") {
             let Ok(entry) = entry else {
                 continue;
             };
-            entry
+            if let Some(id) = entry
                 .path()
                 .file_name()
                 .and_then(|n| n.to_str())
                 .and_then(|n| n.parse().ok())
-                .map(|i| ids.push(i));
+            {
+                ids.push(id);
+            }
         }
 
         Ok(ids)
     }
 
     async fn get_ticket_details(&self, ids: &[u64]) -> Result<Vec<Ticket>, UpstreamError> {
+        self.simulate_request().await?;
+
         Ok(ids.iter().flat_map(|i| self.load_ticket(*i)).collect())
     }
+
+    fn web_url(&self, _id: u64) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_error_rate_is_deterministic() {
+        let adapter = DemoAdapter::new(PathBuf::new()).with_conditions(Duration::ZERO, 0.5);
+
+        let mut results = Vec::new();
+        for _ in 0..4 {
+            results.push(adapter.simulate_request().await.is_ok());
+        }
+        assert_eq!(results, [true, false, true, false]);
+    }
+
+    #[test]
+    fn test_parse_status() {
+        assert_eq!(parse_status("Closed"), TicketStatus::Closed);
+        assert_eq!(parse_status("in progress"), TicketStatus::InProgress);
+        assert_eq!(parse_status(""), TicketStatus::Unknown);
+    }
+
+    #[tokio::test]
+    async fn test_no_errors_by_default() {
+        let adapter = DemoAdapter::new(PathBuf::new());
+
+        for _ in 0..10 {
+            assert!(adapter.simulate_request().await.is_ok());
+        }
+    }
 }
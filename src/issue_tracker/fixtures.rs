@@ -0,0 +1,23 @@
+//! Helpers to test the adapters against recorded responses of real issue trackers.
+//!
+//! The recorded responses live in the `fixtures` folder at the root of the repository.
+//! See `doc/README.md` on how to record new ones.
+
+use std::path::Path;
+
+use wiremock::ResponseTemplate;
+
+/// Load the recorded response body `name` (e.g. `gitlab/issues.json`).
+pub fn fixture(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join(name);
+
+    std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Failed to read fixture '{}': {e}", path.display()))
+}
+
+/// Replay the recorded JSON response `name` with the given HTTP status code.
+pub fn json_response(status: u16, name: &str) -> ResponseTemplate {
+    ResponseTemplate::new(status).set_body_raw(fixture(name), "application/json")
+}
@@ -1,16 +1,26 @@
 use async_trait::async_trait;
-use gitlab::api::{issues::IssueState, AsyncQuery};
+use chrono::{DateTime, Utc};
+use git_url_parse::GitUrl;
+use gitlab::api::{issues::IssueState, paged, ApiError, AsyncQuery, Pagination};
 use secure_string::SecureString;
 use serde::Deserialize;
 use tokio::sync::OnceCell;
 
-use super::{IssueTrackerAdapter, Ticket, UpstreamError};
+use super::{
+    retry::RetryPolicy, IssueTrackerAdapter, Ticket, TicketStatus, Timeouts, UpstreamError,
+};
+use crate::config::IssueStates;
 
 pub struct Gitlab {
     client: OnceCell<gitlab::AsyncGitlab>,
     host: String,
     token: SecureString,
     project: String,
+    /// Connect via plain http instead of https.
+    insecure: bool,
+    retry: RetryPolicy,
+    timeouts: Timeouts,
+    states: IssueStates,
 }
 
 impl Gitlab {
@@ -20,18 +30,47 @@ impl Gitlab {
             host,
             token,
             project,
+            insecure: false,
+            retry: RetryPolicy::default(),
+            timeouts: Timeouts::default(),
+            states: IssueStates::default(),
         }
     }
 
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// The `gitlab` crate brings its own HTTP client instead of the shared [`HttpClient`], which
+    /// has neither timeouts nor the commit-lsp user agent. So every request is limited instead.
+    ///
+    /// [`HttpClient`]: super::HttpClient
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    pub fn with_issue_states(mut self, states: IssueStates) -> Self {
+        self.states = states;
+        self
+    }
+
     async fn client(&self) -> Result<&gitlab::AsyncGitlab, UpstreamError> {
         self.client
             .get_or_try_init(|| async {
-                Ok(
-                    gitlab::GitlabBuilder::new(&self.host, self.token.unsecure())
-                        .build_async()
-                        .await
-                        .expect("Failed to connect to gitlab"),
-                )
+                let mut builder = gitlab::GitlabBuilder::new(&self.host, self.token.unsecure());
+                if self.insecure {
+                    builder.insecure();
+                }
+                self.timeouts
+                    .limit(async {
+                        builder
+                            .build_async()
+                            .await
+                            .map_err(|e| UpstreamError::Request(Box::new(e)))
+                    })
+                    .await
             })
             .await
     }
@@ -40,16 +79,39 @@ impl Gitlab {
 #[async_trait]
 impl IssueTrackerAdapter for Gitlab {
     async fn list_ticket_numbers(&self) -> Result<Vec<u64>, UpstreamError> {
-        let request = gitlab::api::issues::ProjectIssues::builder()
-            .state(IssueState::Opened)
+        let mut builder = gitlab::api::issues::ProjectIssues::builder();
+        match self.states {
+            IssueStates {
+                open: true,
+                closed: false,
+            } => {
+                builder.state(IssueState::Opened);
+            }
+            IssueStates {
+                open: false,
+                closed: true,
+            } => {
+                builder.state(IssueState::Closed);
+            }
+            // no filter lists all issues
+            _ => {}
+        }
+        let request = builder
             .project(&self.project)
             .build()
-            .expect("Failed to build request");
+            .map_err(|e| UpstreamError::Request(Box::new(e)))?;
 
-        let issues: Vec<Issue> = request
-            .query_async(self.client().await?)
-            .await
-            .expect("Expected to get issues");
+        let client = self.client().await?;
+        let issues: Vec<Issue> = self
+            .retry
+            .run(
+                || {
+                    let request = paged(request.clone(), Pagination::All);
+                    async move { self.timeouts.limit(request.query_async(client)).await }
+                },
+                is_transient,
+            )
+            .await?;
 
         Ok(issues.into_iter().map(|i| i.iid).collect())
     }
@@ -59,23 +121,374 @@ impl IssueTrackerAdapter for Gitlab {
             .iids(ids.iter().copied())
             .project(&self.project)
             .build()
-            .expect("Failed to build request");
+            .map_err(|e| UpstreamError::Request(Box::new(e)))?;
 
-        let issues: Vec<Issue> = request
-            .query_async(self.client().await?)
-            .await
-            .expect("Expected to get issues");
+        let client = self.client().await?;
+        let issues: Vec<Issue> = self
+            .retry
+            .run(
+                || self.timeouts.limit(request.query_async(client)),
+                is_transient,
+            )
+            .await?;
+
+        Ok(issues.into_iter().map(Issue::into_ticket).collect())
+    }
+
+    async fn get_merge_request_details(&self, ids: &[u64]) -> Result<Vec<Ticket>, UpstreamError> {
+        let request = gitlab::api::projects::merge_requests::MergeRequests::builder()
+            .iids(ids.iter().copied())
+            .project(&self.project)
+            .build()
+            .map_err(|e| UpstreamError::Request(Box::new(e)))?;
+
+        let client = self.client().await?;
+        let merge_requests: Vec<Issue> = self
+            .retry
+            .run(
+                || self.timeouts.limit(request.query_async(client)),
+                is_transient,
+            )
+            .await?;
 
-        Ok(issues
-            .into_iter()
-            .map(|i| Ticket::new(i.iid, i.title, i.description))
-            .collect())
+        Ok(merge_requests.into_iter().map(Issue::into_ticket).collect())
     }
+
+    fn web_url(&self, id: u64) -> Option<String> {
+        let protocol = if self.insecure { "http" } else { "https" };
+        Some(format!(
+            "{protocol}://{}/{}/-/issues/{id}",
+            self.host, self.project
+        ))
+    }
+}
+
+/// The full path of the project (e.g. `group/subgroup/project`) in the url of the repository.
+///
+/// Gitlab supports nested groups, so the path can contain more than the owner and the name.
+pub fn project_path(url: &GitUrl) -> String {
+    let path = url.path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    percent_encoding::percent_decode_str(path)
+        .decode_utf8_lossy()
+        .into_owned()
 }
 
+/// Whether a failed request might succeed when sent again.
+fn is_transient(error: &UpstreamError) -> bool {
+    match error {
+        UpstreamError::Timeout(_) => true,
+        UpstreamError::Request(e) => e
+            .downcast_ref::<ApiError<gitlab::RestError>>()
+            .is_some_and(is_transient_api_error),
+        UpstreamError::InvalidResponse(_) => false,
+    }
+}
+
+fn is_transient_api_error(error: &ApiError<gitlab::RestError>) -> bool {
+    match error {
+        ApiError::Client {
+            source: gitlab::RestError::Communication { source },
+        } => source.is_connect() || source.is_timeout(),
+        ApiError::GitlabService { status, .. } => {
+            status.is_server_error() || status.as_u16() == 429
+        }
+        _ => false,
+    }
+}
+
+impl From<ApiError<gitlab::RestError>> for UpstreamError {
+    fn from(value: ApiError<gitlab::RestError>) -> Self {
+        Self::Request(Box::new(value))
+    }
+}
+
+/// The fields shared by issues and merge requests.
 #[derive(Deserialize, Clone, Debug)]
 struct Issue {
     iid: u64,
     title: String,
-    description: String,
+    #[serde(default)]
+    description: Option<String>,
+    state: String,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+impl Issue {
+    fn into_ticket(self) -> Ticket {
+        let status = match self.state.as_str() {
+            "opened" => TicketStatus::Open,
+            "closed" | "merged" => TicketStatus::Closed,
+            _ => TicketStatus::Unknown,
+        };
+        Ticket::new(self.iid, self.title, self.description.unwrap_or_default())
+            .with_status(status)
+            .with_updated_at(self.updated_at)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn project_path_from_url() {
+        let project = |url| project_path(&GitUrl::parse(url).unwrap());
+
+        assert_eq!(project("git@gitlab.com:owner/project.git"), "owner/project");
+        assert_eq!(
+            project("https://gitlab.example.com/group/sub/project.git"),
+            "group/sub/project"
+        );
+        assert_eq!(
+            project("ssh://git@gitlab.example.com:2222/group/sub/deeper/project"),
+            "group/sub/deeper/project"
+        );
+        assert_eq!(
+            project("https://gitlab.com/my%20group/project"),
+            "my group/project"
+        );
+    }
+
+    #[test]
+    fn web_url() {
+        let adapter = Gitlab::new(
+            "secret".into(),
+            "gitlab.com".to_owned(),
+            "owner/project".to_owned(),
+        );
+
+        assert_eq!(
+            adapter.web_url(2).as_deref(),
+            Some("https://gitlab.com/owner/project/-/issues/2")
+        );
+    }
+
+    #[cfg(feature = "http-fixtures")]
+    mod http {
+        use std::time::Duration;
+
+        use wiremock::{
+            matchers::{method, path, query_param},
+            Mock, MockServer, ResponseTemplate,
+        };
+
+        use super::*;
+        use crate::issue_tracker::fixtures::json_response;
+
+        const ISSUES_PATH: &str = "/api/v4/projects/owner%2Fproject/issues";
+
+        async fn server() -> MockServer {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/api/v4/user"))
+                .respond_with(json_response(200, "gitlab/user.json"))
+                .mount(&server)
+                .await;
+            server
+        }
+
+        fn adapter(server: &MockServer) -> Gitlab {
+            Gitlab {
+                insecure: true,
+                retry: RetryPolicy {
+                    retries: 2,
+                    base_delay: Duration::from_millis(1),
+                },
+                ..Gitlab::new(
+                    "secret".into(),
+                    server.address().to_string(),
+                    "owner/project".to_owned(),
+                )
+            }
+        }
+
+        #[tokio::test]
+        async fn list_ticket_numbers() {
+            let server = server().await;
+            Mock::given(method("GET"))
+                .and(path(ISSUES_PATH))
+                .respond_with(json_response(200, "gitlab/issues.json"))
+                .mount(&server)
+                .await;
+
+            let ids = adapter(&server).list_ticket_numbers().await.unwrap();
+
+            assert_eq!(ids, [2, 1]);
+        }
+
+        #[tokio::test]
+        async fn list_closed_ticket_numbers() {
+            let server = server().await;
+            Mock::given(method("GET"))
+                .and(path(ISSUES_PATH))
+                .and(query_param("state", "closed"))
+                .respond_with(json_response(200, "gitlab/issues.json"))
+                .expect(1)
+                .mount(&server)
+                .await;
+
+            let ids = adapter(&server)
+                .with_issue_states(IssueStates {
+                    open: false,
+                    closed: true,
+                })
+                .list_ticket_numbers()
+                .await
+                .unwrap();
+
+            assert_eq!(ids, [2, 1]);
+        }
+
+        #[tokio::test]
+        async fn list_ticket_numbers_paginated() {
+            let server = server().await;
+            Mock::given(method("GET"))
+                .and(path(ISSUES_PATH))
+                .and(query_param("page", "1"))
+                .respond_with(json_response(200, "gitlab/issues_page1.json"))
+                .mount(&server)
+                .await;
+            Mock::given(method("GET"))
+                .and(path(ISSUES_PATH))
+                .and(query_param("page", "2"))
+                .respond_with(json_response(200, "gitlab/issues_page2.json"))
+                .mount(&server)
+                .await;
+
+            let ids = adapter(&server).list_ticket_numbers().await.unwrap();
+
+            assert_eq!(ids.len(), 101);
+            assert_eq!(ids.last(), Some(&1));
+        }
+
+        #[tokio::test]
+        async fn get_ticket_details() {
+            let server = server().await;
+            Mock::given(method("GET"))
+                .and(path(ISSUES_PATH))
+                .and(query_param("iids[]", "2"))
+                .respond_with(json_response(200, "gitlab/issue_2.json"))
+                .mount(&server)
+                .await;
+
+            let tickets = adapter(&server).get_ticket_details(&[2]).await.unwrap();
+
+            assert_eq!(tickets.len(), 1);
+            assert_eq!(tickets[0].id(), 2);
+            assert_eq!(tickets[0].title(), "Support gitlab issues");
+            assert_eq!(
+                tickets[0].text(),
+                "Complete gitlab issues in commit messages."
+            );
+            assert_eq!(tickets[0].status(), TicketStatus::Open);
+        }
+
+        #[tokio::test]
+        async fn retries_server_errors() {
+            let server = server().await;
+            Mock::given(method("GET"))
+                .and(path(ISSUES_PATH))
+                .respond_with(ResponseTemplate::new(502))
+                .up_to_n_times(2)
+                .expect(2)
+                .mount(&server)
+                .await;
+            Mock::given(method("GET"))
+                .and(path(ISSUES_PATH))
+                .and(query_param("iids[]", "2"))
+                .respond_with(json_response(200, "gitlab/issue_2.json"))
+                .expect(1)
+                .mount(&server)
+                .await;
+
+            let tickets = adapter(&server).get_ticket_details(&[2]).await.unwrap();
+
+            assert_eq!(tickets.len(), 1);
+        }
+
+        #[tokio::test]
+        async fn slow_server_times_out() {
+            let server = server().await;
+            Mock::given(method("GET"))
+                .and(path(ISSUES_PATH))
+                .respond_with(
+                    json_response(200, "gitlab/issue_2.json").set_delay(Duration::from_secs(5)),
+                )
+                .mount(&server)
+                .await;
+
+            let error = adapter(&server)
+                .with_timeouts(Timeouts {
+                    connect: Duration::from_millis(50),
+                    request: Duration::from_millis(50),
+                })
+                .get_ticket_details(&[2])
+                .await
+                .unwrap_err();
+
+            assert!(matches!(error, UpstreamError::Timeout(_)), "{error}");
+        }
+
+        #[tokio::test]
+        async fn connection_failure() {
+            let server = MockServer::start().await;
+            let adapter = adapter(&server);
+            // nothing listens on the port anymore
+            drop(server);
+
+            let result = adapter.list_ticket_numbers().await;
+
+            assert!(matches!(result, Err(UpstreamError::Request(_))));
+        }
+
+        #[tokio::test]
+        async fn unauthorized() {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .respond_with(json_response(401, "gitlab/unauthorized.json"))
+                .mount(&server)
+                .await;
+
+            let result = adapter(&server).list_ticket_numbers().await;
+
+            assert!(matches!(result, Err(UpstreamError::Request(_))));
+        }
+
+        #[tokio::test]
+        async fn not_found() {
+            let server = server().await;
+            Mock::given(method("GET"))
+                .and(path(ISSUES_PATH))
+                .respond_with(json_response(404, "gitlab/not_found.json"))
+                .mount(&server)
+                .await;
+
+            let result = adapter(&server).list_ticket_numbers().await;
+
+            assert!(matches!(result, Err(UpstreamError::Request(_))));
+        }
+
+        #[tokio::test]
+        async fn get_merge_request_details() {
+            let server = server().await;
+            Mock::given(method("GET"))
+                .and(path("/api/v4/projects/owner%2Fproject/merge_requests"))
+                .and(query_param("iids[]", "3"))
+                .respond_with(json_response(200, "gitlab/merge_request_3.json"))
+                .mount(&server)
+                .await;
+
+            let merge_requests = adapter(&server)
+                .get_merge_request_details(&[3])
+                .await
+                .unwrap();
+
+            assert_eq!(merge_requests.len(), 1);
+            assert_eq!(merge_requests[0].id(), 3);
+            assert_eq!(merge_requests[0].title(), "Reference merge requests");
+            assert_eq!(merge_requests[0].text(), "");
+            assert_eq!(merge_requests[0].status(), TicketStatus::Closed);
+        }
+    }
 }
@@ -0,0 +1,48 @@
+use reqwest::{Method, RequestBuilder};
+
+use super::{Timeouts, UpstreamError};
+
+/// Sent with every request, so administrators of an issue tracker can tell where the requests
+/// come from.
+pub const USER_AGENT: &str = concat!("commit-lsp/", env!("CARGO_PKG_VERSION"));
+
+/// The HTTP client shared by all adapters, so connections to an issue tracker are reused.
+///
+/// Clones share the same connection pool.
+#[derive(Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    timeouts: Timeouts,
+}
+
+impl HttpClient {
+    /// A client that gives up on unresponsive servers.
+    pub fn new(timeouts: Timeouts) -> Self {
+        let client = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .connect_timeout(timeouts.connect)
+            .timeout(timeouts.request)
+            .build()
+            .expect("the TLS backend can be initialized");
+        Self { client, timeouts }
+    }
+
+    pub fn request(&self, method: Method, url: impl reqwest::IntoUrl) -> RequestBuilder {
+        self.client.request(method, url)
+    }
+
+    /// Report timeouts as [`UpstreamError::Timeout`].
+    pub fn convert(&self, error: reqwest::Error) -> UpstreamError {
+        if error.is_timeout() {
+            UpstreamError::Timeout(self.timeouts.request)
+        } else {
+            error.into()
+        }
+    }
+}
+
+impl Default for HttpClient {
+    fn default() -> Self {
+        Self::new(Timeouts::default())
+    }
+}
@@ -0,0 +1,459 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use git_url_parse::{GitUrl, Scheme};
+use reqwest::{Method, RequestBuilder, Response};
+use secure_string::SecureString;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{
+    retry::{self, RetryPolicy},
+    HttpClient, IssueTrackerAdapter, Ticket, TicketStatus, UpstreamError,
+};
+use crate::config::IssueStates;
+
+/// The maximum number of issues Jira Cloud returns per search request.
+const PAGE_SIZE: usize = 100;
+
+/// Jira Cloud, restricted to a single project so that the issue keys (e.g. `PROJ-123`) map to
+/// the numeric ticket ids used everywhere else (e.g. `123`).
+pub struct Jira {
+    client: HttpClient,
+    email: String,
+    api_token: SecureString,
+    /// The root of the Jira site, without trailing slash.
+    base_url: String,
+    /// The project key, e.g. `PROJ`.
+    project: String,
+    retry: RetryPolicy,
+    states: IssueStates,
+}
+
+impl Jira {
+    /// `credentials` are the account email and the API token, separated by a colon.
+    pub fn new(
+        client: HttpClient,
+        credentials: &SecureString,
+        base_url: String,
+        project: String,
+    ) -> Result<Self, String> {
+        let (email, api_token) = credentials
+            .unsecure()
+            .split_once(':')
+            .ok_or("Jira credentials must be given as 'email:api-token'")?;
+        Ok(Self {
+            client,
+            email: email.to_owned(),
+            api_token: api_token.trim().into(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            project,
+            retry: RetryPolicy::default(),
+            states: IssueStates::default(),
+        })
+    }
+
+    /// Use the Jira site and project that the url points to, e.g.
+    /// `https://example.atlassian.net/browse/PROJ` or
+    /// `https://example.atlassian.net/jira/software/projects/PROJ/boards/1`.
+    pub fn from_url(
+        client: HttpClient,
+        credentials: &SecureString,
+        url: &GitUrl,
+    ) -> Result<Self, String> {
+        let protocol = match url.scheme {
+            Scheme::Http => "http",
+            _ => "https",
+        };
+        let host = url.host.as_deref().ok_or("The Jira url has no host")?;
+        let base_url = match url.port {
+            Some(port) => format!("{protocol}://{host}:{port}"),
+            None => format!("{protocol}://{host}"),
+        };
+        let project = project_key(url).ok_or_else(|| {
+            format!("No Jira project in '{url}', expected e.g. '{base_url}/browse/PROJ'")
+        })?;
+        Self::new(client, credentials, base_url, project)
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_issue_states(mut self, states: IssueStates) -> Self {
+        self.states = states;
+        self
+    }
+
+    /// Search the issues of the project matching `condition`.
+    async fn search(
+        &self,
+        condition: &str,
+        fields: &str,
+        start_at: usize,
+    ) -> Result<SearchResult, UpstreamError> {
+        let jql = format!(
+            "project = \"{}\"{condition} ORDER BY key DESC",
+            self.project
+        );
+        let request = self
+            .client
+            .request(Method::GET, format!("{}/rest/api/3/search", self.base_url))
+            .query(&[
+                ("jql", jql.as_str()),
+                ("fields", fields),
+                ("maxResults", &PAGE_SIZE.to_string()),
+                ("startAt", &start_at.to_string()),
+            ])
+            .basic_auth(&self.email, Some(self.api_token.unsecure()));
+
+        Ok(self.send(request).await?.json().await?)
+    }
+
+    /// The numeric id of an issue key of the project, e.g. `123` for `PROJ-123`.
+    fn id(&self, key: &str) -> Option<u64> {
+        key.strip_prefix(&self.project)?
+            .strip_prefix('-')?
+            .parse()
+            .ok()
+    }
+
+    /// Send the request, retrying it on transient errors.
+    async fn send(&self, request: RequestBuilder) -> Result<Response, UpstreamError> {
+        let request = &request;
+        let response = self
+            .retry
+            .run(
+                move || async move {
+                    request
+                        .try_clone()
+                        .expect("requests have no streaming body")
+                        .send()
+                        .await?
+                        .error_for_status()
+                },
+                retry::is_transient,
+            )
+            .await
+            .map_err(|e| self.client.convert(e))?;
+
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl IssueTrackerAdapter for Jira {
+    async fn list_ticket_numbers(&self) -> Result<Vec<u64>, UpstreamError> {
+        let condition = format!(
+            " AND assignee = currentUser(){}",
+            status_condition(self.states)
+        );
+        let mut ids = Vec::new();
+        let mut start_at = 0;
+        loop {
+            let result = self.search(&condition, "summary", start_at).await?;
+
+            let received = result.issues.len();
+            start_at += received;
+            ids.extend(result.issues.iter().filter_map(|i| self.id(&i.key)));
+            if received == 0 || start_at >= result.total {
+                return Ok(ids);
+            }
+        }
+    }
+
+    async fn get_ticket_details(&self, ids: &[u64]) -> Result<Vec<Ticket>, UpstreamError> {
+        let mut tickets = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(PAGE_SIZE) {
+            let keys: Vec<_> = chunk.iter().map(|id| self.reference(*id)).collect();
+            let condition = format!(" AND key IN ({})", keys.join(", "));
+            let result = self
+                .search(&condition, "summary,description,status,updated", 0)
+                .await?;
+            tickets.extend(result.issues.into_iter().filter_map(|issue| {
+                let id = self.id(&issue.key)?;
+                Some(issue.into_ticket(id))
+            }));
+        }
+
+        Ok(tickets)
+    }
+
+    fn web_url(&self, id: u64) -> Option<String> {
+        Some(format!("{}/browse/{}", self.base_url, self.reference(id)))
+    }
+
+    fn reference(&self, id: u64) -> String {
+        format!("{}-{id}", self.project)
+    }
+}
+
+/// The project key in urls like `https://example.atlassian.net/browse/PROJ`.
+fn project_key(url: &GitUrl) -> Option<String> {
+    let mut segments = url.path.split('/').filter(|s| !s.is_empty());
+    segments.find(|&s| s == "browse" || s == "projects")?;
+    let key = segments.next()?;
+    // `browse/PROJ-123` links to an issue of the project
+    let key = key.split_once('-').map_or(key, |(project, _)| project);
+    Some(key.to_owned())
+}
+
+/// JQL condition restricting the issues to the given states.
+fn status_condition(states: IssueStates) -> &'static str {
+    match (states.open, states.closed) {
+        (true, false) => " AND statusCategory != Done",
+        (false, true) => " AND statusCategory = Done",
+        _ => "",
+    }
+}
+
+/// Convert a description in the Atlassian Document Format to plain text.
+///
+/// Paragraphs, headings and list items are kept on separate lines, all formatting is dropped.
+fn adf_to_text(node: &Value) -> String {
+    fn walk(node: &Value, out: &mut String) {
+        match node["type"].as_str() {
+            Some("text") => out.push_str(node["text"].as_str().unwrap_or_default()),
+            Some("hardBreak") => out.push('\n'),
+            Some("mention" | "emoji") => {
+                out.push_str(node["attrs"]["text"].as_str().unwrap_or_default())
+            }
+            Some("inlineCard") => out.push_str(node["attrs"]["url"].as_str().unwrap_or_default()),
+            Some(kind) => {
+                if kind == "listItem" {
+                    out.push_str("- ");
+                }
+                for child in node["content"].as_array().into_iter().flatten() {
+                    walk(child, out);
+                }
+                match kind {
+                    "paragraph" | "heading" | "codeBlock" | "rule" => out.push_str("\n\n"),
+                    "bulletList" | "orderedList" => out.push('\n'),
+                    _ => {}
+                }
+            }
+            None => {}
+        }
+    }
+
+    let mut text = String::new();
+    walk(node, &mut text);
+    // list items consist of paragraphs, which would leave blank lines between the items
+    let text = text.replace("\n\n- ", "\n- ");
+    crate::regex!(r"\n{3,}")
+        .replace_all(&text, "\n\n")
+        .trim()
+        .to_owned()
+}
+
+#[derive(Deserialize, Debug)]
+struct SearchResult {
+    issues: Vec<Issue>,
+    total: usize,
+}
+
+#[derive(Deserialize, Debug)]
+struct Issue {
+    key: String,
+    fields: Fields,
+}
+
+#[derive(Deserialize, Debug)]
+struct Fields {
+    summary: String,
+    #[serde(default)]
+    description: Value,
+    status: Option<Status>,
+    updated: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Status {
+    status_category: StatusCategory,
+}
+
+#[derive(Deserialize, Debug)]
+struct StatusCategory {
+    key: String,
+}
+
+impl Issue {
+    fn into_ticket(self, id: u64) -> Ticket {
+        let status = match self.fields.status.map(|s| s.status_category.key).as_deref() {
+            Some("new") => TicketStatus::Open,
+            Some("indeterminate") => TicketStatus::InProgress,
+            Some("done") => TicketStatus::Closed,
+            _ => TicketStatus::Unknown,
+        };
+        let updated_at = self
+            .fields
+            .updated
+            .and_then(|d| DateTime::parse_from_str(&d, "%Y-%m-%dT%H:%M:%S%.f%z").ok())
+            .map(|d| d.with_timezone(&Utc));
+        Ticket::new(
+            id,
+            self.fields.summary,
+            adf_to_text(&self.fields.description),
+        )
+        .with_status(status)
+        .with_updated_at(updated_at)
+    }
+}
+
+#[cfg(all(test, feature = "http-fixtures"))]
+mod test {
+    use std::time::Duration;
+
+    use wiremock::{
+        matchers::{header, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::issue_tracker::fixtures::json_response;
+
+    fn adapter(server: &MockServer) -> Jira {
+        Jira::new(
+            HttpClient::default(),
+            &"me@example.com:secret".into(),
+            server.uri(),
+            "PROJ".to_owned(),
+        )
+        .unwrap()
+        .with_retry(RetryPolicy {
+            retries: 2,
+            base_delay: Duration::from_millis(1),
+        })
+    }
+
+    #[tokio::test]
+    async fn list_ticket_numbers() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/api/3/search"))
+            // `me@example.com:secret` in base64
+            .and(header(
+                "authorization",
+                "Basic bWVAZXhhbXBsZS5jb206c2VjcmV0",
+            ))
+            .and(query_param(
+                "jql",
+                "project = \"PROJ\" AND assignee = currentUser() AND statusCategory != Done \
+                 ORDER BY key DESC",
+            ))
+            .respond_with(json_response(200, "jira/search.json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let ids = adapter(&server).list_ticket_numbers().await.unwrap();
+
+        assert_eq!(ids, [123, 45]);
+    }
+
+    #[tokio::test]
+    async fn get_ticket_details() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/api/3/search"))
+            .and(query_param(
+                "jql",
+                "project = \"PROJ\" AND key IN (PROJ-123, PROJ-45) ORDER BY key DESC",
+            ))
+            .respond_with(json_response(200, "jira/search.json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let tickets = adapter(&server)
+            .get_ticket_details(&[123, 45])
+            .await
+            .unwrap();
+
+        assert_eq!(tickets.len(), 2);
+        assert_eq!(tickets[0].id(), 123);
+        assert_eq!(tickets[0].title(), "Support Jira issue keys");
+        assert_eq!(
+            tickets[0].text(),
+            "Hover over PROJ-123 shows this.\nSecond line\n\nSteps:\n- open a commit\n- hover"
+        );
+        assert_eq!(tickets[0].status(), TicketStatus::InProgress);
+        assert_eq!(
+            tickets[0].updated_at().unwrap().to_rfc3339(),
+            "2024-05-20T16:30:12.540+00:00"
+        );
+        assert_eq!(tickets[1].id(), 45);
+        assert_eq!(tickets[1].text(), "");
+        assert_eq!(tickets[1].status(), TicketStatus::Open);
+    }
+
+    #[tokio::test]
+    async fn unauthorized() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let result = adapter(&server).list_ticket_numbers().await;
+
+        assert!(matches!(result, Err(UpstreamError::Request(_))));
+    }
+
+    #[test]
+    fn invalid_credentials() {
+        let result = Jira::new(
+            HttpClient::default(),
+            &"only-a-token".into(),
+            "https://example.atlassian.net".to_owned(),
+            "PROJ".to_owned(),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn project_from_url() {
+        let url = |u| GitUrl::parse(u).unwrap();
+
+        assert_eq!(
+            project_key(&url("https://example.atlassian.net/browse/PROJ")),
+            Some("PROJ".to_owned())
+        );
+        assert_eq!(
+            project_key(&url("https://example.atlassian.net/browse/PROJ-12")),
+            Some("PROJ".to_owned())
+        );
+        assert_eq!(
+            project_key(&url(
+                "https://example.atlassian.net/jira/software/projects/PROJ/boards/1"
+            )),
+            Some("PROJ".to_owned())
+        );
+        assert_eq!(
+            project_key(&url("https://example.atlassian.net/jira")),
+            None
+        );
+    }
+
+    #[test]
+    fn web_url_and_reference() {
+        let adapter = Jira::new(
+            HttpClient::default(),
+            &"me@example.com:secret".into(),
+            "https://example.atlassian.net/".to_owned(),
+            "PROJ".to_owned(),
+        )
+        .unwrap();
+
+        assert_eq!(adapter.reference(7), "PROJ-7");
+        assert_eq!(adapter.id("PROJ-7"), Some(7));
+        assert_eq!(adapter.id("OTHER-7"), None);
+        assert_eq!(
+            adapter.web_url(7).as_deref(),
+            Some("https://example.atlassian.net/browse/PROJ-7")
+        );
+    }
+}
@@ -1,10 +1,24 @@
-use std::{collections::BTreeMap, ffi::OsStr, process::Command, sync::Mutex};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    process::Command,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 
 mod azure;
 mod demo;
+#[cfg(all(test, feature = "http-fixtures"))]
+mod fixtures;
 mod gitlab;
+mod http;
+mod jira;
+mod redmine;
+mod retry;
+mod timeout;
 
 use azure::AzureDevops;
 use git_url_parse::GitUrl;
@@ -12,15 +26,24 @@ use secure_string::SecureString;
 use tracing::{info, warn};
 
 use crate::{
-    config,
+    analysis::Reference,
+    config::{self, IssueTrackerType},
     healthcheck::{HealthReport, ResultExt},
+    regex,
 };
 
-use self::{demo::DemoAdapter, gitlab::Gitlab};
+pub use self::http::{HttpClient, USER_AGENT};
+pub use self::retry::RetryPolicy;
+pub use self::timeout::Timeouts;
+use self::{demo::DemoAdapter, gitlab::Gitlab, jira::Jira, redmine::Redmine};
 
 pub struct IssueTracker {
     remote: Box<dyn IssueTrackerAdapter>,
     ticket_cache: Mutex<BTreeMap<u64, Ticket>>,
+    merge_request_cache: Mutex<BTreeMap<u64, Ticket>>,
+    /// Tickets that upstream does not know, so that they are not requested again.
+    missing_tickets: Mutex<BTreeSet<u64>>,
+    updated_within: Option<Duration>,
 }
 
 impl IssueTracker {
@@ -31,39 +54,95 @@ impl IssueTracker {
     ) -> Option<Self> {
         if cfg!(debug_assertions) && std::env::var("COMMIT_LSP_DEMO_FOLDER").is_ok() {
             let folder = std::env::var("COMMIT_LSP_DEMO_FOLDER").unwrap();
-            return Some(Self {
-                remote: Box::new(DemoAdapter::new(folder.into())),
-                ticket_cache: Default::default(),
-            });
+            return Some(Self::from_adapter(Box::new(
+                DemoAdapter::new(folder.into()).with_env_conditions(),
+            )));
+        }
+        let remote =
+            lookup_remote(&url.to_string(), config).report(health, "lookup credential command")?;
+        let retry = remote.retry_policy();
+        let timeouts = remote.timeouts();
+        // created once, so all requests share the connection pool
+        let client = HttpClient::new(timeouts);
+
+        let (states, unknown_states) = remote.issue_states();
+        let check = health.start("issue states");
+        if unknown_states.is_empty() {
+            check.ok();
+        } else {
+            warn!(?unknown_states, "Ignoring unknown issue states");
+            check.warn(format!(
+                "Ignoring unknown issue states {unknown_states:?}, expected 'open' or 'closed'"
+            ));
         }
-        let cred_command = lookup_credential_command(&url.to_string(), &config)
-            .report(health, "lookup credential command")?;
 
-        info!("Got credential command: {cred_command:?}");
-        let adapter: Box<dyn IssueTrackerAdapter> = match url.host?.as_str() {
-            "ssh.dev.azure.com" | "dev.azure.com" => {
-                let pat = get_credentials(&cred_command).report(health, "retrieve credentials")?;
-                Box::new(AzureDevops::new(pat, url.organization?, url.owner?))
+        let host = url.host.as_deref()?;
+        let Some(tracker_type) = remote.issue_tracker_type.or_else(|| detect_type(host)) else {
+            warn!(host, "Unsupported host! No issue autocompletion available");
+            return None;
+        };
+
+        let adapter: Box<dyn IssueTrackerAdapter> = match tracker_type {
+            IssueTrackerType::AzureDevops => {
+                let pat = get_secret(remote).report(health, "retrieve credentials")?;
+                Box::new(
+                    AzureDevops::new(client, pat, url.organization?, url.owner?)
+                        .with_retry(retry)
+                        .with_issue_states(states),
+                )
             }
-            host if host.contains("gitlab") => {
-                let token =
-                    get_credentials(&cred_command).report(health, "retrieve credentials")?;
-                let project = format!("{}/{}", url.owner?, url.name);
-                Box::new(Gitlab::new(token, host.to_owned(), project))
+            IssueTrackerType::Gitlab => {
+                let token = get_secret(remote).report(health, "retrieve credentials")?;
+                let project = gitlab::project_path(&url);
+                Box::new(
+                    Gitlab::new(token, host.to_owned(), project)
+                        .with_retry(retry)
+                        .with_timeouts(timeouts)
+                        .with_issue_states(states),
+                )
             }
-            url => {
-                warn!(
-                    host = url.to_string(),
-                    "Unsupported host! No issue autocompletion available"
-                );
-                return None;
+            IssueTrackerType::Redmine => {
+                let api_key = get_secret(remote).report(health, "retrieve credentials")?;
+                Box::new(
+                    Redmine::from_url(client, api_key, &url)?
+                        .with_retry(retry)
+                        .with_issue_states(states),
+                )
+            }
+            IssueTrackerType::Jira => {
+                let credentials = get_secret(remote).report(health, "retrieve credentials")?;
+                let check = health.start("configure Jira project");
+                match Jira::from_url(client, &credentials, &url) {
+                    Ok(jira) => {
+                        check.ok();
+                        Box::new(jira.with_retry(retry).with_issue_states(states))
+                    }
+                    Err(e) => {
+                        check.error(e);
+                        return None;
+                    }
+                }
             }
         };
 
-        Some(Self {
-            remote: adapter,
+        Some(Self::from_adapter(adapter))
+    }
+
+    /// Use a custom adapter, e.g. for an issue tracker that is not supported out of the box.
+    pub fn from_adapter(remote: Box<dyn IssueTrackerAdapter>) -> Self {
+        Self {
+            remote,
             ticket_cache: Default::default(),
-        })
+            merge_request_cache: Default::default(),
+            missing_tickets: Default::default(),
+            updated_within: None,
+        }
+    }
+
+    /// Only list tickets that were updated within the given time span.
+    /// Tickets without update information are always listed.
+    pub fn set_updated_within(&mut self, span: Option<Duration>) {
+        self.updated_within = span;
     }
 
     pub async fn request_ticket_information(&self) -> Result<Vec<Ticket>, UpstreamError> {
@@ -84,18 +163,68 @@ impl IssueTracker {
             .lock()
             .unwrap()
             .values()
+            .filter(|t| self.is_updated_within(t))
             .cloned()
             .collect()
     }
 
+    /// Whether `ticket` was updated within the span of [`Self::set_updated_within`].
+    pub fn is_updated_within(&self, ticket: &Ticket) -> bool {
+        let cutoff = self
+            .updated_within
+            .and_then(|span| chrono::Duration::from_std(span).ok())
+            .map(|span| Utc::now() - span);
+
+        match (cutoff, ticket.updated_at()) {
+            (Some(cutoff), Some(updated_at)) => updated_at >= cutoff,
+            _ => true,
+        }
+    }
+
+    /// Look up the ticket for a reference in the commit message.
+    /// Returns `None` for references without a numeric ID, as no tracker supports them yet.
+    pub async fn get_referenced_ticket(
+        &self,
+        reference: &Reference,
+    ) -> Result<Option<Ticket>, UpstreamError> {
+        match reference.id() {
+            Some(id) => self.get_ticket_details(id).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Get the ticket from the cache, without asking upstream.
+    pub fn cached_ticket(&self, id: u64) -> Option<Ticket> {
+        self.ticket_cache.lock().unwrap().get(&id).cloned()
+    }
+
+    /// Whether upstream was asked for the ticket and did not know it.
+    pub fn is_missing(&self, id: u64) -> bool {
+        self.missing_tickets.lock().unwrap().contains(&id)
+    }
+
+    /// The URL to view the given ticket in a web browser.
+    pub fn web_url(&self, id: u64) -> Option<String> {
+        self.remote.web_url(id)
+    }
+
+    /// How the ticket is referenced in a commit message, e.g. `#123` or `PROJ-123`.
+    pub fn reference(&self, id: u64) -> String {
+        self.remote.reference(id)
+    }
+
     pub async fn get_ticket_details(&self, id: u64) -> Result<Option<Ticket>, UpstreamError> {
         if let Some(ticket) = self.ticket_cache.lock().unwrap().get(&id) {
             return Ok(Some(ticket.clone()));
         }
+        if self.is_missing(id) {
+            return Ok(None);
+        }
 
         let tickets = self.remote.get_ticket_details(&[id]).await?;
 
         let Some(ticket) = tickets.first() else {
+            self.missing_tickets.lock().unwrap().insert(id);
             return Ok(None);
         };
 
@@ -104,6 +233,24 @@ impl IssueTracker {
 
         Ok(Some(ticket.clone()))
     }
+
+    /// Get title and description of a merge request, for trackers that support them.
+    pub async fn get_merge_request(&self, id: u64) -> Result<Option<Ticket>, UpstreamError> {
+        if let Some(merge_request) = self.merge_request_cache.lock().unwrap().get(&id) {
+            return Ok(Some(merge_request.clone()));
+        }
+
+        let merge_requests = self.remote.get_merge_request_details(&[id]).await?;
+        let Some(merge_request) = merge_requests.into_iter().find(|mr| mr.id() == id) else {
+            return Ok(None);
+        };
+
+        self.merge_request_cache
+            .lock()
+            .unwrap()
+            .insert(id, merge_request.clone());
+        Ok(Some(merge_request))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -111,11 +258,29 @@ pub struct Ticket {
     id: u64,
     title: String,
     text: String,
+    status: TicketStatus,
+    updated_at: Option<DateTime<Utc>>,
 }
 
 impl Ticket {
-    pub(super) fn new(id: u64, title: String, text: String) -> Self {
-        Self { id, title, text }
+    pub fn new(id: u64, title: String, text: String) -> Self {
+        Self {
+            id,
+            title,
+            text,
+            status: TicketStatus::Unknown,
+            updated_at: None,
+        }
+    }
+
+    pub fn with_status(mut self, status: TicketStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn with_updated_at(mut self, updated_at: Option<DateTime<Utc>>) -> Self {
+        self.updated_at = updated_at;
+        self
     }
 
     pub fn id(&self) -> u64 {
@@ -129,33 +294,161 @@ impl Ticket {
     pub fn text(&self) -> &str {
         self.text.as_ref()
     }
+
+    pub fn status(&self) -> TicketStatus {
+        self.status
+    }
+
+    /// The last time this ticket was modified, if known.
+    pub fn updated_at(&self) -> Option<DateTime<Utc>> {
+        self.updated_at
+    }
+}
+
+/// The state of a ticket in its workflow, simplified across all issue trackers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TicketStatus {
+    Open,
+    InProgress,
+    Closed,
+    #[default]
+    Unknown,
+}
+
+impl std::fmt::Display for TicketStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            TicketStatus::Open => "open",
+            TicketStatus::InProgress => "in progress",
+            TicketStatus::Closed => "closed",
+            TicketStatus::Unknown => "unknown",
+        };
+        f.write_str(text)
+    }
 }
 
 #[derive(Debug)]
-pub enum UpstreamError {}
+pub enum UpstreamError {
+    /// Sending the request failed or the issue tracker answered with an error.
+    Request(Box<dyn std::error::Error + Send + Sync>),
+    /// The issue tracker answered with data in an unexpected format.
+    InvalidResponse(String),
+    /// The issue tracker did not answer in time.
+    Timeout(Duration),
+}
 
 impl std::fmt::Display for UpstreamError {
-    fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        unreachable!()
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpstreamError::Request(e) => write!(f, "Request to issue tracker failed: {e}"),
+            UpstreamError::InvalidResponse(msg) => {
+                write!(f, "Unexpected response from issue tracker: {msg}")
+            }
+            UpstreamError::Timeout(timeout) => {
+                write!(f, "Issue tracker did not answer within {timeout:?}")
+            }
+        }
     }
 }
 
 impl std::error::Error for UpstreamError {}
 
+impl From<reqwest::Error> for UpstreamError {
+    fn from(value: reqwest::Error) -> Self {
+        Self::Request(Box::new(value))
+    }
+}
+
+/// The connection to one kind of issue tracker.
 #[async_trait]
-trait IssueTrackerAdapter: Send + Sync {
+pub trait IssueTrackerAdapter: Send + Sync {
     async fn list_ticket_numbers(&self) -> Result<Vec<u64>, UpstreamError>;
 
     /// Request additional detail (like title or description) for the given IDs from upstream.
     /// If any IDs are invalid, then they will not be included in the result Vec.
     async fn get_ticket_details(&self, ids: &[u64]) -> Result<Vec<Ticket>, UpstreamError>;
+
+    /// The URL to view the given ticket in a web browser, if the tracker has a web interface.
+    fn web_url(&self, id: u64) -> Option<String>;
+
+    /// Request details of merge requests, represented as tickets.
+    /// Trackers without merge requests return an empty Vec.
+    async fn get_merge_request_details(&self, _ids: &[u64]) -> Result<Vec<Ticket>, UpstreamError> {
+        Ok(Vec::new())
+    }
+
+    /// How the ticket is referenced in a commit message.
+    fn reference(&self, id: u64) -> String {
+        format!("#{id}")
+    }
+}
+
+/// Get the access token for the remote, either from the credentials command or, if none is
+/// configured, from the environment variable `token_env`.
+fn get_secret(remote: &config::Remote) -> Option<SecureString> {
+    let cred_command = &remote.credentials_command;
+    if !cred_command.is_empty() {
+        info!("Got credential command: {cred_command:?}");
+        return get_credentials(cred_command, remote.credentials_cache_duration());
+    }
+
+    let name = remote.token_env();
+    match std::env::var(name) {
+        Ok(token) if !token.is_empty() => {
+            // never log the token itself
+            info!(name, "Using token from environment variable");
+            Some(token.into())
+        }
+        _ => {
+            warn!(
+                name,
+                "Neither credentials command nor token environment variable available"
+            );
+            None
+        }
+    }
 }
 
-fn get_credentials(cmdline: &[impl AsRef<OsStr>]) -> Option<SecureString> {
+/// Output of credentials commands, together with the time the command ran.
+/// Kept in memory only, so that secrets are never written to disk.
+static CREDENTIALS_CACHE: Mutex<BTreeMap<Vec<String>, (SecureString, Instant)>> =
+    Mutex::new(BTreeMap::new());
+
+/// Get the output of the credentials command, running it only if there is no cached output
+/// younger than `cache_for` (`None` caches for the lifetime of the process).
+fn get_credentials(cmdline: &[String], cache_for: Option<Duration>) -> Option<SecureString> {
+    let is_fresh = |created: &Instant| cache_for.is_none_or(|ttl| created.elapsed() < ttl);
+    if let Some((credentials, _)) = CREDENTIALS_CACHE
+        .lock()
+        .unwrap()
+        .get(cmdline)
+        .filter(|(_, created)| is_fresh(created))
+    {
+        return Some(credentials.clone());
+    }
+
+    // failures are not cached, so that the next attempt runs the command again
+    let credentials = run_credentials_command(cmdline)?;
+    CREDENTIALS_CACHE
+        .lock()
+        .unwrap()
+        .insert(cmdline.to_vec(), (credentials.clone(), Instant::now()));
+    Some(credentials)
+}
+
+/// Run the credentials command and return its output.
+///
+/// Environment variables (`$VAR` or `${VAR}`) in the arguments are expanded. The command is
+/// not run by a shell, so no other shell syntax like quoting or globs is evaluated.
+fn run_credentials_command(cmdline: &[String]) -> Option<SecureString> {
     let pat = {
+        let cmdline: Vec<_> = cmdline.iter().map(|arg| expand_env(arg)).collect();
         let (cmd, args) = cmdline.split_first()?;
 
-        let out = Command::new(cmd).args(args).output().unwrap();
+        let out = Command::new(cmd.as_ref())
+            .args(args.iter().map(AsRef::as_ref))
+            .output()
+            .unwrap();
         if !out.status.success() {
             let stderr = String::from_utf8_lossy(&out.stderr).to_string();
             let code = out.status.code();
@@ -167,13 +460,175 @@ fn get_credentials(cmdline: &[impl AsRef<OsStr>]) -> Option<SecureString> {
     Some(pat)
 }
 
+/// Replace `$VAR` and `${VAR}` with the value of the environment variable.
+/// Unset variables are replaced by the empty string, like a shell would do.
+fn expand_env(arg: &str) -> Cow<'_, str> {
+    regex!(r"\$(?:\{([A-Za-z_][A-Za-z0-9_]*)\}|([A-Za-z_][A-Za-z0-9_]*))").replace_all(
+        arg,
+        |caps: &regex::Captures| {
+            let name = caps
+                .get(1)
+                .or(caps.get(2))
+                .expect("one alternative matched");
+            std::env::var(name.as_str()).unwrap_or_else(|_| {
+                warn!(
+                    name = name.as_str(),
+                    "Undefined variable in credentials command"
+                );
+                String::new()
+            })
+        },
+    )
+}
+
 #[tracing::instrument]
-fn lookup_credential_command(url: &str, config: &config::User) -> Option<Vec<String>> {
+/// The issue tracker on the given host, if it can be told from the name.
+fn detect_type(host: &str) -> Option<IssueTrackerType> {
+    match host {
+        "ssh.dev.azure.com" | "dev.azure.com" => Some(IssueTrackerType::AzureDevops),
+        host if host.contains("gitlab") => Some(IssueTrackerType::Gitlab),
+        host if host.ends_with(".atlassian.net") => Some(IssueTrackerType::Jira),
+        _ => None,
+    }
+}
+
+fn lookup_remote<'a>(url: &str, config: &'a config::User) -> Option<&'a config::Remote> {
     info!(url, "searching for host info");
     config
         .remotes
         .iter()
         .find(|r| url.contains(&r.host))
         .inspect(|r| info!("Using remote {r:?}"))
-        .map(|r| r.credentials_command.clone())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn expand_env_variables() {
+        std::env::set_var("COMMIT_LSP_TEST_DIR", "/home/me");
+
+        assert_eq!(
+            expand_env("$COMMIT_LSP_TEST_DIR/token and ${COMMIT_LSP_TEST_DIR}_2"),
+            "/home/me/token and /home/me_2"
+        );
+        assert_eq!(expand_env("${COMMIT_LSP_TEST_UNDEFINED}x"), "x");
+        assert_eq!(expand_env("no variables"), "no variables");
+    }
+
+    #[test]
+    fn credentials_command_is_expanded() {
+        std::env::set_var("COMMIT_LSP_TEST_TOKEN", "secret");
+
+        let token =
+            run_credentials_command(&["echo".to_owned(), "${COMMIT_LSP_TEST_TOKEN}".to_owned()]);
+
+        assert_eq!(token.unwrap().unsecure(), "secret");
+    }
+
+    /// Knows no tickets at all and counts how often it is asked.
+    #[derive(Default)]
+    struct EmptyAdapter {
+        requests: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl IssueTrackerAdapter for EmptyAdapter {
+        async fn list_ticket_numbers(&self) -> Result<Vec<u64>, UpstreamError> {
+            Ok(Vec::new())
+        }
+
+        async fn get_ticket_details(&self, _ids: &[u64]) -> Result<Vec<Ticket>, UpstreamError> {
+            self.requests
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(Vec::new())
+        }
+
+        fn web_url(&self, _id: u64) -> Option<String> {
+            None
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_tickets_are_cached() {
+        let adapter = EmptyAdapter::default();
+        let requests = adapter.requests.clone();
+        let tracker = IssueTracker::from_adapter(Box::new(adapter));
+
+        assert!(!tracker.is_missing(9999));
+        assert!(tracker.get_ticket_details(9999).await.unwrap().is_none());
+        assert!(tracker.get_ticket_details(9999).await.unwrap().is_none());
+
+        assert!(tracker.is_missing(9999));
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn token_from_environment() {
+        std::env::set_var("COMMIT_LSP_TEST_ENV_TOKEN", "from env");
+        let remote: config::Remote =
+            toml::from_str("host = 'x'\ntoken_env = 'COMMIT_LSP_TEST_ENV_TOKEN'").unwrap();
+        assert_eq!(get_secret(&remote).unwrap().unsecure(), "from env");
+
+        // an explicit command takes precedence
+        let remote = config::Remote {
+            credentials_command: vec!["echo".to_owned(), "from command".to_owned()],
+            ..remote
+        };
+        assert_eq!(get_secret(&remote).unwrap().unsecure(), "from command");
+
+        let remote: config::Remote =
+            toml::from_str("host = 'x'\ntoken_env = 'COMMIT_LSP_TEST_UNDEFINED'").unwrap();
+        assert!(get_secret(&remote).is_none());
+    }
+
+    /// A credentials command that counts its invocations in the given file.
+    fn counting_command(counter: &std::path::Path, exit_code: u8) -> Vec<String> {
+        vec![
+            "sh".to_owned(),
+            "-c".to_owned(),
+            format!(
+                "echo run >> '{}'; echo token; exit {exit_code}",
+                counter.display()
+            ),
+        ]
+    }
+
+    fn invocations(counter: &std::path::Path) -> usize {
+        std::fs::read_to_string(counter).map_or(0, |s| s.lines().count())
+    }
+
+    #[test]
+    fn credentials_are_cached() {
+        let counter = std::env::temp_dir().join(format!("commit-lsp-cred-{}", std::process::id()));
+        let command = counting_command(&counter, 0);
+
+        let first = get_credentials(&command, Some(Duration::from_secs(60)));
+        let second = get_credentials(&command, Some(Duration::from_secs(60)));
+        let runs_within_ttl = invocations(&counter);
+        let expired = get_credentials(&command, Some(Duration::ZERO));
+        let runs_after_ttl = invocations(&counter);
+        std::fs::remove_file(&counter).unwrap();
+
+        assert_eq!(first.unwrap().unsecure(), "token");
+        assert_eq!(second.unwrap().unsecure(), "token");
+        assert!(expired.is_some());
+        assert_eq!(runs_within_ttl, 1);
+        assert_eq!(runs_after_ttl, 2);
+    }
+
+    #[test]
+    fn failed_credentials_are_not_cached() {
+        let counter =
+            std::env::temp_dir().join(format!("commit-lsp-cred-fail-{}", std::process::id()));
+        let command = counting_command(&counter, 1);
+
+        assert!(get_credentials(&command, None).is_none());
+        assert!(get_credentials(&command, None).is_none());
+        let runs = invocations(&counter);
+        std::fs::remove_file(&counter).unwrap();
+
+        assert_eq!(runs, 2);
+    }
 }
@@ -0,0 +1,349 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use git_url_parse::{GitUrl, Scheme};
+use reqwest::{Method, RequestBuilder, Response};
+use secure_string::SecureString;
+use serde::Deserialize;
+
+use super::{
+    retry::{self, RetryPolicy},
+    HttpClient, IssueTrackerAdapter, Ticket, TicketStatus, UpstreamError,
+};
+use crate::config::IssueStates;
+
+/// The maximum number of issues Redmine returns per request.
+const PAGE_SIZE: usize = 100;
+
+pub struct Redmine {
+    client: HttpClient,
+    api_key: SecureString,
+    /// The root of the Redmine instance, without trailing slash.
+    base_url: String,
+    /// Identifier of the project the issues are listed for, all projects if not set.
+    project: Option<String>,
+    retry: RetryPolicy,
+    states: IssueStates,
+}
+
+impl Redmine {
+    pub fn new(
+        client: HttpClient,
+        api_key: SecureString,
+        base_url: String,
+        project: Option<String>,
+    ) -> Self {
+        Self {
+            client,
+            api_key,
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            project,
+            retry: RetryPolicy::default(),
+            states: IssueStates::default(),
+        }
+    }
+
+    /// Use the Redmine instance and project that the url points to, e.g.
+    /// `https://redmine.example.com/projects/website`.
+    pub fn from_url(client: HttpClient, api_key: SecureString, url: &GitUrl) -> Option<Self> {
+        let protocol = match url.scheme {
+            Scheme::Http => "http",
+            _ => "https",
+        };
+        let host = url.host.as_deref()?;
+        let base_url = match url.port {
+            Some(port) => format!("{protocol}://{host}:{port}"),
+            None => format!("{protocol}://{host}"),
+        };
+        Some(Self::new(
+            client,
+            api_key,
+            base_url,
+            project_identifier(url),
+        ))
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_issue_states(mut self, states: IssueStates) -> Self {
+        self.states = states;
+        self
+    }
+
+    fn issues_request(&self) -> RequestBuilder {
+        let mut request = self
+            .client
+            .request(Method::GET, format!("{}/issues.json", self.base_url))
+            .header("X-Redmine-API-Key", self.api_key.unsecure());
+        if let Some(project) = &self.project {
+            request = request.query(&[("project_id", project)]);
+        }
+        request
+    }
+
+    /// Send the request, retrying it on transient errors.
+    async fn send(&self, request: RequestBuilder) -> Result<Response, UpstreamError> {
+        let request = &request;
+        let response = self
+            .retry
+            .run(
+                move || async move {
+                    request
+                        .try_clone()
+                        .expect("requests have no streaming body")
+                        .send()
+                        .await?
+                        .error_for_status()
+                },
+                retry::is_transient,
+            )
+            .await
+            .map_err(|e| self.client.convert(e))?;
+
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl IssueTrackerAdapter for Redmine {
+    async fn list_ticket_numbers(&self) -> Result<Vec<u64>, UpstreamError> {
+        let mut ids = Vec::new();
+        loop {
+            let request = self.issues_request().query(&[
+                ("assigned_to_id", "me"),
+                ("status_id", status_filter(self.states)),
+                ("limit", &PAGE_SIZE.to_string()),
+                ("offset", &ids.len().to_string()),
+            ]);
+            let page: IssuePage = self.send(request).await?.json().await?;
+
+            let received = page.issues.len();
+            ids.extend(page.issues.into_iter().map(|i| i.id));
+            if received == 0 || ids.len() >= page.total_count {
+                return Ok(ids);
+            }
+        }
+    }
+
+    async fn get_ticket_details(&self, ids: &[u64]) -> Result<Vec<Ticket>, UpstreamError> {
+        let mut tickets = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(PAGE_SIZE) {
+            let ids: Vec<_> = chunk.iter().map(u64::to_string).collect();
+            let request = self.issues_request().query(&[
+                ("issue_id", ids.join(",").as_str()),
+                // details are also needed for closed issues
+                ("status_id", "*"),
+                ("limit", &PAGE_SIZE.to_string()),
+            ]);
+            let page: IssuePage = self.send(request).await?.json().await?;
+            tickets.extend(page.issues.into_iter().map(Issue::into_ticket));
+        }
+
+        Ok(tickets)
+    }
+
+    fn web_url(&self, id: u64) -> Option<String> {
+        Some(format!("{}/issues/{id}", self.base_url))
+    }
+}
+
+/// The project identifier in urls like `https://redmine.example.com/projects/website/issues`.
+fn project_identifier(url: &GitUrl) -> Option<String> {
+    let mut segments = url.path.split('/').filter(|s| !s.is_empty());
+    segments.find(|&s| s == "projects")?;
+    segments.next().map(str::to_owned)
+}
+
+/// The `status_id` filter of Redmine for the issues to list.
+fn status_filter(states: IssueStates) -> &'static str {
+    match (states.open, states.closed) {
+        (true, false) => "open",
+        (false, true) => "closed",
+        _ => "*",
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct IssuePage {
+    issues: Vec<Issue>,
+    total_count: usize,
+}
+
+#[derive(Deserialize, Debug)]
+struct Issue {
+    id: u64,
+    subject: String,
+    #[serde(default)]
+    description: Option<String>,
+    status: Option<Status>,
+    updated_on: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Status {
+    name: String,
+    /// Only sent by Redmine 5.1 and newer.
+    is_closed: Option<bool>,
+}
+
+impl Issue {
+    fn into_ticket(self) -> Ticket {
+        let status = self
+            .status
+            .map_or(TicketStatus::Unknown, |s| s.ticket_status());
+        Ticket::new(self.id, self.subject, self.description.unwrap_or_default())
+            .with_status(status)
+            .with_updated_at(self.updated_on)
+    }
+}
+
+impl Status {
+    /// Redmine statuses are freely configurable, so only the default ones are known.
+    fn ticket_status(&self) -> TicketStatus {
+        match (self.name.as_str(), self.is_closed) {
+            ("In Progress", _) => TicketStatus::InProgress,
+            (_, Some(true)) | ("Closed" | "Rejected" | "Resolved", None) => TicketStatus::Closed,
+            (_, Some(false)) | ("New" | "Feedback", None) => TicketStatus::Open,
+            _ => TicketStatus::Unknown,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "http-fixtures"))]
+mod test {
+    use std::time::Duration;
+
+    use wiremock::{
+        matchers::{header, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::issue_tracker::fixtures::json_response;
+
+    fn adapter(server: &MockServer) -> Redmine {
+        Redmine::new(
+            HttpClient::default(),
+            "secret".into(),
+            server.uri(),
+            Some("website".to_owned()),
+        )
+        .with_retry(RetryPolicy {
+            retries: 2,
+            base_delay: Duration::from_millis(1),
+        })
+    }
+
+    #[tokio::test]
+    async fn list_ticket_numbers() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/issues.json"))
+            .and(header("X-Redmine-API-Key", "secret"))
+            .and(query_param("assigned_to_id", "me"))
+            .and(query_param("status_id", "open"))
+            .and(query_param("project_id", "website"))
+            .respond_with(json_response(200, "redmine/issues.json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let ids = adapter(&server).list_ticket_numbers().await.unwrap();
+
+        assert_eq!(ids, [12, 7]);
+    }
+
+    #[tokio::test]
+    async fn get_ticket_details() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/issues.json"))
+            .and(query_param("issue_id", "12,7"))
+            .and(query_param("status_id", "*"))
+            .respond_with(json_response(200, "redmine/issues.json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let tickets = adapter(&server).get_ticket_details(&[12, 7]).await.unwrap();
+
+        assert_eq!(tickets.len(), 2);
+        assert_eq!(tickets[0].id(), 12);
+        assert_eq!(tickets[0].title(), "Add a dark theme");
+        assert_eq!(tickets[0].text(), "The website is too bright at night.");
+        assert_eq!(tickets[0].status(), TicketStatus::InProgress);
+        assert_eq!(
+            tickets[0].updated_at().unwrap().to_rfc3339(),
+            "2024-06-03T14:12:09+00:00"
+        );
+        assert_eq!(tickets[1].id(), 7);
+        assert_eq!(tickets[1].text(), "");
+        assert_eq!(tickets[1].status(), TicketStatus::Open);
+    }
+
+    #[tokio::test]
+    async fn unauthorized() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let result = adapter(&server).list_ticket_numbers().await;
+
+        assert!(matches!(result, Err(UpstreamError::Request(_))));
+    }
+
+    #[test]
+    fn web_url() {
+        let adapter = Redmine::new(
+            HttpClient::default(),
+            "secret".into(),
+            "https://redmine.example.com/".to_owned(),
+            None,
+        );
+
+        assert_eq!(
+            adapter.web_url(42).as_deref(),
+            Some("https://redmine.example.com/issues/42")
+        );
+    }
+
+    #[test]
+    fn project_from_url() {
+        let url = |u| GitUrl::parse(u).unwrap();
+
+        assert_eq!(
+            project_identifier(&url("https://redmine.example.com/projects/website")),
+            Some("website".to_owned())
+        );
+        assert_eq!(
+            project_identifier(&url("https://example.com/redmine/projects/website/issues")),
+            Some("website".to_owned())
+        );
+        assert_eq!(
+            project_identifier(&url("https://redmine.example.com/issues")),
+            None
+        );
+    }
+
+    #[test]
+    fn status_mapping() {
+        let status = |name: &str, is_closed| {
+            Status {
+                name: name.to_owned(),
+                is_closed,
+            }
+            .ticket_status()
+        };
+
+        assert_eq!(status("In Progress", Some(false)), TicketStatus::InProgress);
+        assert_eq!(status("Done", Some(true)), TicketStatus::Closed);
+        assert_eq!(status("Waiting", Some(false)), TicketStatus::Open);
+        assert_eq!(status("Rejected", None), TicketStatus::Closed);
+        assert_eq!(status("Waiting", None), TicketStatus::Unknown);
+    }
+}
@@ -0,0 +1,132 @@
+use std::{future::Future, time::Duration};
+
+use tracing::warn;
+
+/// How often requests are repeated after transient failures, like connection problems or an
+/// overloaded server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Number of retries after the initial attempt.
+    pub retries: u32,
+    /// Delay before the first retry, doubled for every further retry.
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 2,
+            base_delay: Duration::from_millis(250),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Run `request` until it succeeds, fails with an error that is not `transient` or the
+    /// retries are exhausted.
+    pub async fn run<T, E, Fut>(
+        &self,
+        mut request: impl FnMut() -> Fut,
+        transient: impl Fn(&E) -> bool,
+    ) -> Result<T, E>
+    where
+        Fut: Future<Output = Result<T, E>>,
+        E: std::fmt::Display,
+    {
+        let mut delay = self.base_delay;
+        for _ in 0..self.retries {
+            match request().await {
+                Err(e) if transient(&e) => {
+                    warn!("Request failed, retrying in {delay:?}: {e}");
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+
+        request().await
+    }
+}
+
+/// Whether a failed request might succeed when sent again.
+/// Authentication problems and other client errors are permanent.
+pub fn is_transient(error: &reqwest::Error) -> bool {
+    match error.status() {
+        Some(status) => {
+            status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        }
+        None => error.is_connect() || error.is_timeout(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn policy(retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            retries,
+            base_delay: Duration::from_millis(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_transient_errors() {
+        let attempts = Cell::new(0);
+
+        let result = policy(2)
+            .run(
+                || async {
+                    attempts.set(attempts.get() + 1);
+                    if attempts.get() < 3 {
+                        Err("transient")
+                    } else {
+                        Ok(attempts.get())
+                    }
+                },
+                |_| true,
+            )
+            .await;
+
+        assert_eq!(result, Ok(3));
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_retries() {
+        let attempts = Cell::new(0);
+
+        let result: Result<(), _> = policy(2)
+            .run(
+                || async {
+                    attempts.set(attempts.get() + 1);
+                    Err("transient")
+                },
+                |_| true,
+            )
+            .await;
+
+        assert_eq!(result, Err("transient"));
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_permanent_errors() {
+        let attempts = Cell::new(0);
+
+        let result: Result<(), _> = policy(2)
+            .run(
+                || async {
+                    attempts.set(attempts.get() + 1);
+                    Err("unauthorized")
+                },
+                |_| false,
+            )
+            .await;
+
+        assert_eq!(result, Err("unauthorized"));
+        assert_eq!(attempts.get(), 1);
+    }
+}
@@ -0,0 +1,65 @@
+use std::{future::Future, time::Duration};
+
+use super::UpstreamError;
+
+/// How long to wait for an issue tracker before giving up on a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Time to establish the connection.
+    pub connect: Duration,
+    /// Time for the whole request, from connecting until the response body is read.
+    pub request: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_secs(10),
+            request: Duration::from_secs(10),
+        }
+    }
+}
+
+impl Timeouts {
+    /// Fail with [`UpstreamError::Timeout`] if `request` takes longer than the request timeout.
+    ///
+    /// For clients other than [`HttpClient`](super::HttpClient), which has the timeouts built in.
+    pub async fn limit<T, E: Into<UpstreamError>>(
+        &self,
+        request: impl Future<Output = Result<T, E>>,
+    ) -> Result<T, UpstreamError> {
+        match tokio::time::timeout(self.request, request).await {
+            Ok(result) => result.map_err(Into::into),
+            Err(_) => Err(UpstreamError::Timeout(self.request)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn limit_stops_slow_requests() {
+        let timeouts = Timeouts {
+            connect: Duration::from_millis(10),
+            request: Duration::from_millis(10),
+        };
+
+        let result: Result<(), _> = timeouts
+            .limit(async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok::<_, UpstreamError>(())
+            })
+            .await;
+
+        assert!(matches!(result, Err(UpstreamError::Timeout(_))));
+        assert_eq!(
+            timeouts
+                .limit(async { Ok::<_, UpstreamError>(1) })
+                .await
+                .ok(),
+            Some(1)
+        );
+    }
+}
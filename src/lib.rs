@@ -0,0 +1,59 @@
+//! Linting of git commit messages in the conventional commit format.
+//!
+//! This is the library behind the `commit-lsp` language server. To check a message, use [`lint`]:
+//!
+//! ```
+//! let diagnostics = commit_lsp::lint(commit_lsp::Repository::default(), "feat: add a thing.");
+//! assert_eq!(diagnostics[0].to_string(), "[1:18] The subject should not end with a period [subject-trailing-period]");
+//! ```
+//!
+//! For repeated analysis of a changing message (and the features of the language server like
+//! completion or hover), keep a [`State`] around.
+//!
+//! The issue tracker integration needs an async runtime and is only available with the
+//! `issue-tracker` feature (enabled by default). Lint-only users can disable the default features.
+
+// stdout carries the language server protocol, only `HealthReport` may print to it
+#![deny(clippy::print_stdout)]
+
+pub mod analysis;
+pub mod config;
+pub mod git;
+pub mod healthcheck;
+#[cfg(feature = "issue-tracker")]
+pub mod issue_tracker;
+pub mod patterns;
+pub mod text_util;
+
+pub use analysis::{Diagnostic, State};
+pub use config::Repository;
+
+/// Check the commit message `text` against the rules of `config`.
+///
+/// Checks that need the git repository (like `check_scope_matches_staged`) or the issue
+/// tracker (like `check_references_exist`) find nothing to report.
+pub fn lint(config: Repository, text: &str) -> Vec<Diagnostic> {
+    let mut state = State::new(config);
+    state.update_text(text);
+    state.all_diagnostics()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lint_reports_problems() {
+        assert!(lint(Repository::default(), "feat: add a thing").is_empty());
+
+        let config = Repository {
+            types_requiring_scope: vec!["feat".to_owned()],
+            ..Default::default()
+        };
+        let codes: Vec<_> = lint(config, "feat: add a thing\nbody")
+            .iter()
+            .filter_map(|d| d.report().code)
+            .collect();
+        assert_eq!(codes, ["empty-second-line", "missing-scope"]);
+    }
+}
@@ -1,55 +1,204 @@
+// stdout carries the protocol, anything else printed there breaks the connection
+#![deny(clippy::print_stdout)]
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use tower_lsp::lsp_types::{
-    CompletionItem, CompletionItemKind, CompletionItemLabelDetails, CompletionParams,
-    CompletionResponse, DidChangeTextDocumentParams, DidOpenTextDocumentParams, Documentation,
-    Hover, HoverContents, HoverParams, HoverProviderCapability, InitializeParams, InitializeResult,
-    InitializedParams, MarkedString, ServerCapabilities, ServerInfo, TextDocumentSyncCapability,
-    TextDocumentSyncKind, WorkDoneProgressOptions,
+    ClientCapabilities, CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams,
+    CodeActionProviderCapability, CodeActionResponse, CodeLens, CodeLensOptions, CodeLensParams,
+    Command, CompletionItem, CompletionItemKind, CompletionItemLabelDetails, CompletionParams,
+    CompletionResponse, DidChangeConfigurationParams, DidChangeTextDocumentParams,
+    DidChangeWatchedFilesParams, DidChangeWatchedFilesRegistrationOptions,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DocumentFormattingParams,
+    DocumentSymbolParams, DocumentSymbolResponse, Documentation, FileSystemWatcher, GlobPattern,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, InitializeParams, InitializeResult, InitializedParams, InlayHint,
+    InlayHintLabel, InlayHintParams, InsertTextFormat, MarkupContent, MarkupKind, MessageType,
+    OneOf, Position, PositionEncodingKind, Range, Registration, RenameParams, SemanticToken,
+    SemanticTokenType, SemanticTokens, SemanticTokensFullOptions, SemanticTokensLegend,
+    SemanticTokensOptions, SemanticTokensParams, SemanticTokensResult,
+    SemanticTokensServerCapabilities, ServerCapabilities, ServerInfo, ShowDocumentParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url, WorkDoneProgressOptions,
+    WorkspaceEdit,
 };
 
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
-use tracing::info;
+use tracing::{info, warn};
 
-use crate::analysis::{self, ItemKind};
-use crate::issue_tracker::IssueTracker;
-use crate::text_util::Ellipse as _;
+use commit_lsp::analysis::{self, HeaderPart, ItemKind, PositionEncoding, Reference};
+use commit_lsp::config;
+use commit_lsp::git::{get_commit_template, get_staged_files};
+use commit_lsp::issue_tracker::{IssueTracker, Ticket, TicketStatus};
+use commit_lsp::text_util::Ellipse as _;
 
 struct Backend {
     client: Client,
-    analysis: Mutex<analysis::State>,
+    documents: Documents,
     tracker: Option<Arc<IssueTracker>>,
+    fetch_debouncer: Debouncer,
+    diagnostics_debouncer: Debouncer,
+    ticket_inlay_hints: bool,
+    /// The repository configuration given on the command line, if any.
+    config_path: Option<PathBuf>,
+    /// Merged into the repository configuration when it is reloaded.
+    user_config: config::User,
+}
+
+/// The token types used for highlighting, the position in this list is sent to the client.
+const TOKEN_TYPES: [SemanticTokenType; 6] = [
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::OPERATOR,
+    SemanticTokenType::STRING,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::PROPERTY,
+];
+
+/// The analysis of every open commit message, keyed by the document URI.
+#[derive(Clone)]
+struct Documents {
+    /// Newly opened documents start as a copy of this state, it holds the current configuration.
+    template: Arc<Mutex<analysis::State>>,
+    open: Arc<Mutex<HashMap<Url, analysis::State>>>,
+}
+
+impl Documents {
+    fn new(template: analysis::State) -> Self {
+        Self {
+            template: Arc::new(template.into()),
+            open: Default::default(),
+        }
+    }
+
+    /// Start analysing a document, replacing a previous document with the same URI.
+    fn open(&self, uri: Url, text: &str) {
+        let mut analysis = self.template.lock().unwrap().clone();
+        analysis.update_text(text);
+        self.open.lock().unwrap().insert(uri, analysis);
+    }
+
+    /// Forget the analysis of a document, returns whether the document was open.
+    fn close(&self, uri: &Url) -> bool {
+        self.open.lock().unwrap().remove(uri).is_some()
+    }
+
+    /// Run `f` on the analysis of a document, `None` if the document is not open.
+    fn with<T>(&self, uri: &Url, f: impl FnOnce(&mut analysis::State) -> T) -> Option<T> {
+        self.open.lock().unwrap().get_mut(uri).map(f)
+    }
+
+    /// Use the configuration for all open and all future documents.
+    ///
+    /// Returns the URIs of the open documents.
+    fn set_config(&self, config: config::Repository) -> Vec<Url> {
+        let mut open = self.open.lock().unwrap();
+        for analysis in open.values_mut() {
+            analysis.set_config(config.clone());
+        }
+        self.template.lock().unwrap().set_config(config);
+        open.keys().cloned().collect()
+    }
+
+    /// Count positions of all open and all future documents in `encoding`.
+    fn set_position_encoding(&self, encoding: PositionEncoding) {
+        for analysis in self.open.lock().unwrap().values_mut() {
+            analysis.set_position_encoding(encoding);
+        }
+        self.template
+            .lock()
+            .unwrap()
+            .set_position_encoding(encoding);
+    }
+}
+
+/// Delays actions until nothing new was scheduled for the same document for a given cooldown
+/// period.
+///
+/// Scheduling a new action cancels all pending ones of the document that have not started yet,
+/// the actions of other documents are not affected.
+struct Debouncer {
+    cooldown: Duration,
+    generations: Arc<Mutex<HashMap<Url, u64>>>,
+}
+
+impl Debouncer {
+    fn new(cooldown: Duration) -> Self {
+        Self {
+            cooldown,
+            generations: Default::default(),
+        }
+    }
+
+    fn schedule(&self, uri: Url, action: impl Future<Output = ()> + Send + 'static) {
+        let generation = {
+            let mut generations = self.generations.lock().unwrap();
+            let generation = generations.entry(uri.clone()).or_default();
+            *generation += 1;
+            *generation
+        };
+        let latest = self.generations.clone();
+        let cooldown = self.cooldown;
+
+        tokio::spawn(async move {
+            tokio::time::sleep(cooldown).await;
+            let is_latest = latest.lock().unwrap().get(&uri) == Some(&generation);
+            if is_latest {
+                action.await;
+            }
+        });
+    }
+
+    /// Cancel the pending actions of a closed document.
+    fn forget(&self, uri: &Url) {
+        self.generations.lock().unwrap().remove(uri);
+    }
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
-        Ok(InitializeResult {
-            capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
-                )),
-                hover_provider: Some(HoverProviderCapability::Simple(true)),
-                completion_provider: Some(tower_lsp::lsp_types::CompletionOptions {
-                    resolve_provider: Some(false),
-                    trigger_characters: Some(vec!["#".to_owned(), "(".to_owned()]),
-                    all_commit_characters: None,
-                    work_done_progress_options: WorkDoneProgressOptions {
-                        work_done_progress: None,
-                    },
-                    completion_item: None,
-                }),
-                ..Default::default()
-            },
-            server_info: Some(ServerInfo {
-                name: "commit-lsp".to_owned(),
-                version: Some("0.0.1".to_owned()),
-            }),
-        })
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        let encoding = negotiate_position_encoding(&params.capabilities);
+        self.documents.set_position_encoding(encoding);
+        Ok(initialize_result(
+            self.tracker.is_some(),
+            self.ticket_inlay_hints,
+            encoding,
+        ))
     }
 
     async fn initialized(&self, _: InitializedParams) {
+        // Clients that do not support dynamic registration still send
+        // `workspace/didChangeConfiguration`, which reloads the config as well.
+        let patterns = match &self.config_path {
+            Some(path) => vec![path.display().to_string()],
+            None => config::Repository::FILE_NAMES
+                .iter()
+                .map(|name| format!("**/{name}"))
+                .collect(),
+        };
+        let watcher = DidChangeWatchedFilesRegistrationOptions {
+            watchers: patterns
+                .into_iter()
+                .map(|pattern| FileSystemWatcher {
+                    glob_pattern: GlobPattern::String(pattern),
+                    kind: None,
+                })
+                .collect(),
+        };
+        let registration = Registration {
+            id: "watch-config".to_owned(),
+            method: "workspace/didChangeWatchedFiles".to_owned(),
+            register_options: serde_json::to_value(watcher).ok(),
+        };
+        if let Err(e) = self.client.register_capability(vec![registration]).await {
+            info!("Failed to watch the config file: {e}");
+        }
+
         if let Some(tracker) = &self.tracker {
             let tracker = tracker.clone();
             tokio::spawn(async move {
@@ -64,52 +213,99 @@ impl LanguageServer for Backend {
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        let text = params.text_document.text;
-
-        let diags;
-        {
-            let mut analysis = self.analysis.lock().unwrap();
+        let uri = params.text_document.uri;
+        self.documents.open(uri.clone(), &params.text_document.text);
 
-            analysis.update_text(&text);
-            diags = analysis
-                .all_diagnostics()
-                .into_iter()
-                .map(|d| d.into())
-                .collect();
-        }
+        let Some((diags, references, summary)) = self.documents.with(&uri, |analysis| {
+            (
+                diagnostics(analysis, self.tracker.as_deref()),
+                analysis.referenced_tickets(),
+                analysis.summary(),
+            )
+        }) else {
+            return;
+        };
         self.client
-            .publish_diagnostics(params.text_document.uri, diags, None)
+            .publish_diagnostics(uri.clone(), diags, None)
             .await;
+
+        // only on open, a summary after every edit would be noise
+        match self.user_config.show_open_summary {
+            config::OpenSummary::Off => {}
+            config::OpenSummary::Log => self.client.log_message(MessageType::INFO, summary).await,
+            config::OpenSummary::Message => {
+                self.client.show_message(MessageType::INFO, summary).await
+            }
+        }
+
+        self.fetch_references(uri, references);
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
-        let text = &params
-            .content_changes
-            .first()
-            .expect("expected to get full document")
-            .text;
-
-        let diags;
-        {
-            let mut analysis = self.analysis.lock().unwrap();
-
-            analysis.update_text(&text);
-            diags = analysis
-                .all_diagnostics()
-                .into_iter()
-                .map(|d| d.into())
-                .collect();
+        let uri = params.text_document.uri;
+        let Some(references) = self.documents.with(&uri, |analysis| {
+            for change in &params.content_changes {
+                match change.range {
+                    Some(range) => analysis.apply_change(range, &change.text),
+                    None => analysis.update_text(&change.text),
+                };
+            }
+            analysis.referenced_tickets()
+        }) else {
+            warn!("Change of document {uri} that was never opened");
+            return;
+        };
+
+        // Only publish the diagnostics once the user pauses typing. The diagnostics are computed
+        // when publishing, so the latest text is always checked.
+        let documents = self.documents.clone();
+        let tracker = self.tracker.clone();
+        let client = self.client.clone();
+        let changed = uri.clone();
+        self.diagnostics_debouncer
+            .schedule(uri.clone(), async move {
+                let diags = documents.with(&changed, |analysis| {
+                    diagnostics(analysis, tracker.as_deref())
+                });
+                // the document may have been closed in the meantime
+                if let Some(diags) = diags {
+                    client.publish_diagnostics(changed, diags, None).await;
+                }
+            });
+
+        self.fetch_references(uri, references);
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        if !self.documents.close(&uri) {
+            return;
         }
-        self.client
-            .publish_diagnostics(params.text_document.uri, diags, None)
-            .await;
+        self.diagnostics_debouncer.forget(&uri);
+        self.fetch_debouncer.forget(&uri);
+
+        // Some clients keep showing the diagnostics of closed documents otherwise.
+        self.client.publish_diagnostics(uri, Vec::new(), None).await;
+    }
+
+    async fn did_change_configuration(&self, _: DidChangeConfigurationParams) {
+        self.reload_config().await;
+    }
+
+    async fn did_change_watched_files(&self, _: DidChangeWatchedFilesParams) {
+        self.reload_config().await;
     }
 
     async fn hover(&self, par: HoverParams) -> Result<Option<Hover>> {
         info!("Hover request");
+        let uri = par.text_document_position_params.text_document.uri;
         let pos = par.text_document_position_params.position;
 
-        let Some(item) = self.analysis.lock().unwrap().lookup(pos) else {
+        let Some(item) = self
+            .documents
+            .with(&uri, |analysis| analysis.lookup(pos))
+            .flatten()
+        else {
             return Ok(None);
         };
 
@@ -117,80 +313,418 @@ impl LanguageServer for Backend {
 
         match item.kind {
             ItemKind::Ty => {
-                let analysis = self.analysis.lock().unwrap();
-                let Some(info) = analysis.commit_type_info() else {
+                let Some(info) = self
+                    .documents
+                    .with(&uri, |analysis| analysis.commit_type_info())
+                    .flatten()
+                else {
                     return Ok(None);
                 };
 
-                return Ok(Some(Hover {
-                    contents: HoverContents::Scalar(MarkedString::String(format!(
-                        "# {}\n\n{}",
-                        info.summary, info.description
-                    ))),
-                    range: Some(item.range),
-                }));
+                return Ok(Some(markdown_hover(
+                    format!("# {}\n\n{}", info.summary, info.description),
+                    item.range,
+                )));
             }
             ItemKind::Scope => {
-                let analysis = self.analysis.lock().unwrap();
-                let Some(info) = analysis.commit_scope_info() else {
+                let Some(info) = self
+                    .documents
+                    .with(&uri, |analysis| analysis.commit_scope_info())
+                    .flatten()
+                else {
                     return Ok(None);
                 };
 
-                return Ok(Some(Hover {
-                    contents: HoverContents::Scalar(MarkedString::String(format!(
-                        "# {}\n\n{}",
-                        info.summary, info.description
-                    ))),
-                    range: Some(item.range),
-                }));
+                return Ok(Some(markdown_hover(
+                    format!("# {}\n\n{}", info.summary, info.description),
+                    item.range,
+                )));
             }
-            ItemKind::Ref(id) => {
+            ItemKind::BreakingChange => {
+                return Ok(Some(markdown_hover(
+                    "# Breaking Change\n\n\
+                         This commit introduces a breaking change, for example to an API or \
+                         the configuration. The text following the colon describes the change \
+                         and how users need to adapt."
+                        .to_owned(),
+                    item.range,
+                )));
+            }
+            ItemKind::Breaking => {
+                return Ok(Some(markdown_hover(
+                    "# Breaking Change\n\n\
+                         The `!` marks this commit as a breaking change: users of the project \
+                         have to adapt when they upgrade. Tools deriving versions from commits \
+                         bump the major version.\n\n\
+                         Describe the change in a `BREAKING CHANGE:` footer."
+                        .to_owned(),
+                    item.range,
+                )));
+            }
+            ItemKind::Trailer => {
+                let Some(help) = trailer_documentation(&item.text) else {
+                    return Ok(None);
+                };
+
+                return Ok(Some(markdown_hover(
+                    format!("# {}\n\n{help}", item.text),
+                    item.range,
+                )));
+            }
+            ItemKind::Revert => {
+                return Ok(Some(markdown_hover(
+                    format!(
+                        "# Revert\n\nThis commit reverts the commit with the subject\n\n> {}",
+                        item.text
+                    ),
+                    item.range,
+                )));
+            }
+            ItemKind::Gitmoji => {
+                let Some((emoji, code, help)) = gitmoji_documentation(&item.text) else {
+                    return Ok(None);
+                };
+
+                return Ok(Some(markdown_hover(
+                    format!("# {emoji} `{code}`\n\n{help}"),
+                    item.range,
+                )));
+            }
+            ItemKind::Ref(reference) => {
                 if let Some(tracker) = &self.tracker {
-                    let ticket = tracker
-                        .get_ticket_details(id)
-                        .await
-                        .expect("To connect to remote");
-
-                    let text = ticket
-                        .map(|t| format!("# {}\n\n{}", t.title(), t.text()))
-                        .unwrap_or_else(|| format!("#{id} not found!"));
-
-                    return Ok(Some(Hover {
-                        contents: HoverContents::Scalar(MarkedString::String(text)),
-                        range: Some(item.range),
-                    }));
+                    let text = ticket_hover_text(tracker, &reference).await;
+
+                    return Ok(Some(markdown_hover(text, item.range)));
                 }
             }
+            ItemKind::MergeRequest(id) => {
+                if let Some(tracker) = &self.tracker {
+                    let text = match tracker.get_merge_request(id).await {
+                        Ok(Some(merge_request)) => ticket_markdown(&merge_request),
+                        Ok(None) => format!("{} not found!", item.text),
+                        Err(e) => {
+                            warn!("Failed to retrieve merge request {}: {e}", item.text);
+                            format!("Failed to fetch {}: {e}", item.text)
+                        }
+                    };
+
+                    return Ok(Some(markdown_hover(text, item.range)));
+                }
+            }
+        }
+
+        Ok(Some(markdown_hover(item.text, item.range)))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri;
+
+        let actions: Vec<_> = self
+            .documents
+            .with(&uri, |analysis| {
+                params
+                    .context
+                    .diagnostics
+                    .iter()
+                    .flat_map(|diag| {
+                        analysis
+                            .quick_fixes(diag)
+                            .into_iter()
+                            .map(|(title, edits)| {
+                                CodeActionOrCommand::CodeAction(CodeAction {
+                                    title,
+                                    kind: Some(CodeActionKind::QUICKFIX),
+                                    diagnostics: Some(vec![diag.clone()]),
+                                    edit: Some(WorkspaceEdit::new(
+                                        [(uri.clone(), edits)].into_iter().collect(),
+                                    )),
+                                    ..Default::default()
+                                })
+                            })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if actions.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(actions))
+    }
+
+    /// Summarize the status of the referenced tickets above the subject, as far as they are
+    /// known already.
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let Some(tracker) = &self.tracker else {
+            return Ok(None);
+        };
+
+        let Some(mut ids) = self.documents.with(&params.text_document.uri, |analysis| {
+            analysis.referenced_tickets()
+        }) else {
+            return Ok(None);
+        };
+        ids.sort_unstable();
+        ids.dedup();
+        let statuses: Vec<_> = ids
+            .into_iter()
+            .filter_map(|id| tracker.cached_ticket(id))
+            .map(|t| t.status())
+            .collect();
+
+        let Some(summary) = ticket_status_summary(&statuses) else {
+            return Ok(None);
+        };
+        Ok(Some(vec![CodeLens {
+            range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+            // only informational, there is nothing to run
+            command: Some(Command::new(summary, String::new(), None)),
+            data: None,
+        }]))
+    }
+
+    /// Show the titles of referenced tickets, as far as they are known already.
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let Some(tracker) = self.tracker.as_ref().filter(|_| self.ticket_inlay_hints) else {
+            return Ok(None);
+        };
+
+        let references: Vec<_> = self
+            .documents
+            .with(&params.text_document.uri, |analysis| {
+                analysis.references().cloned().collect()
+            })
+            .unwrap_or_default();
+        let hints: Vec<_> = references
+            .iter()
+            .filter(|r| params.range.start <= r.range.end && r.range.start <= params.range.end)
+            .filter_map(|r| {
+                let ticket = tracker.cached_ticket(r.id()?)?;
+                Some(InlayHint {
+                    position: r.range.end,
+                    label: InlayHintLabel::String(format!(
+                        "‹{}›",
+                        ticket.title().truncate_ellipse_with(40, "…")
+                    )),
+                    kind: None,
+                    text_edits: None,
+                    tooltip: None,
+                    padding_left: Some(true),
+                    padding_right: None,
+                    data: None,
+                })
+            })
+            .collect();
+
+        Ok(Some(hints))
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let symbols = self.documents.with(&params.text_document.uri, |analysis| {
+            analysis.document_symbols()
+        });
+        Ok(symbols.map(DocumentSymbolResponse::Nested))
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        let Some(tokens) = self.documents.with(&params.text_document.uri, |analysis| {
+            analysis.semantic_tokens()
+        }) else {
+            return Ok(None);
+        };
+        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: None,
+            data: encode_semantic_tokens(&tokens),
+        })))
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let edit = self
+            .documents
+            .with(&params.text_document.uri, |analysis| analysis.format())
+            .flatten();
+        Ok(edit.map(|edit| vec![edit]))
+    }
+
+    /// Add the ticket description to a ticket completion item.
+    async fn completion_resolve(&self, mut item: CompletionItem) -> Result<CompletionItem> {
+        let (Some(tracker), Some(id)) = (
+            &self.tracker,
+            item.data.as_ref().and_then(|data| data.as_u64()),
+        ) else {
+            return Ok(item);
+        };
+
+        match tracker.get_ticket_details(id).await {
+            Ok(Some(ticket)) => {
+                item.documentation = Some(Documentation::String(ticket.text().to_owned()));
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Failed to retrieve ticket #{id}: {e}"),
         }
 
-        Ok(Some(Hover {
-            contents: HoverContents::Scalar(MarkedString::String(item.text)),
-            range: Some(item.range),
+        Ok(item)
+    }
+
+    /// Change the ticket reference under the cursor everywhere in the message.
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri;
+        let pos = params.text_document_position.position;
+        let edits = self
+            .documents
+            .with(&uri, |analysis| {
+                analysis.rename_reference(pos, &params.new_name)
+            })
+            .transpose()
+            .map_err(tower_lsp::jsonrpc::Error::invalid_params)?
+            .flatten();
+
+        Ok(edits.map(|edits| WorkspaceEdit {
+            changes: Some(HashMap::from([(uri, edits)])),
+            ..Default::default()
         }))
     }
 
+    /// Open the ticket under the cursor in the web browser.
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let pos = params.text_document_position_params.position;
+        let Some(item) = self
+            .documents
+            .with(uri, |analysis| analysis.lookup(pos))
+            .flatten()
+        else {
+            return Ok(None);
+        };
+        let ItemKind::Ref(reference) = item.kind else {
+            return Ok(None);
+        };
+        let Some(tracker) = &self.tracker else {
+            return Ok(None);
+        };
+        let Some(url) = reference.id().and_then(|id| tracker.web_url(id)) else {
+            return Ok(None);
+        };
+        let uri = match Url::parse(&url) {
+            Ok(uri) => uri,
+            Err(e) => {
+                warn!("Invalid ticket url '{url}': {e}");
+                return Ok(None);
+            }
+        };
+
+        // The ticket is not a location inside the editor, so ask the client to open it externally.
+        self.client
+            .show_document(ShowDocumentParams {
+                uri,
+                external: Some(true),
+                take_focus: Some(true),
+                selection: None,
+            })
+            .await?;
+
+        Ok(None)
+    }
+
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
-        if params.text_document_position.position.line == 0 {
-            let analysis = self.analysis.lock().unwrap();
-            let items = if params
-                .context
-                .is_some_and(|c| c.trigger_character.is_some_and(|c| c == "("))
-            {
-                analysis.get_commit_scopes()
+        let uri = &params.text_document_position.text_document.uri;
+        let pos = params.text_document_position.position;
+        let Some((empty_document_line_ending, header_part)) =
+            self.documents.with(uri, |analysis| {
+                (
+                    analysis.is_empty().then(|| analysis.line_ending()),
+                    analysis.header_part(pos),
+                )
+            })
+        else {
+            return Ok(None);
+        };
+        if let Some(line_ending) = empty_document_line_ending {
+            // Offer a whole message skeleton to get started.
+            let mut items = Vec::new();
+            let template = tokio::task::spawn_blocking(get_commit_template).await;
+            if let Ok(Some(template)) = template {
+                items.push(CompletionItem {
+                    label: "template".to_owned(),
+                    detail: Some("Commit template from `commit.template`".to_owned()),
+                    kind: Some(CompletionItemKind::SNIPPET),
+                    insert_text: Some(line_ending.normalize(&template)),
+                    insert_text_format: Some(InsertTextFormat::PLAIN_TEXT),
+                    documentation: Some(Documentation::String(template)),
+                    ..Default::default()
+                });
+            }
+            items.push(CompletionItem {
+                label: "skeleton".to_owned(),
+                detail: Some("Conventional commit skeleton".to_owned()),
+                kind: Some(CompletionItemKind::SNIPPET),
+                insert_text: Some(
+                    line_ending.normalize("${1:type}(${2:scope}): ${3:subject}\n\n${0:body}"),
+                ),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            });
+
+            return Ok(Some(CompletionResponse::Array(items)));
+        }
+
+        if let Some(part @ (HeaderPart::Type | HeaderPart::Scope)) = header_part {
+            let complete_scope = part == HeaderPart::Scope;
+            let staged_files = if complete_scope {
+                // git might be slow in large repositories, do not block the other requests
+                tokio::task::spawn_blocking(get_staged_files)
+                    .await
+                    .unwrap_or_default()
             } else {
-                analysis.get_commit_types()
+                Vec::new()
             };
+
+            let items = self
+                .documents
+                .with(uri, |analysis| {
+                    if complete_scope {
+                        analysis
+                            .rank_commit_scopes(&staged_files)
+                            .into_iter()
+                            .map(|(ty, suggested)| (ty.clone(), suggested))
+                            .collect()
+                    } else {
+                        analysis
+                            .get_commit_types()
+                            .iter()
+                            .map(|ty| (ty.clone(), false))
+                            .collect::<Vec<_>>()
+                    }
+                })
+                .unwrap_or_default();
             let items: Vec<_> = items
-                .iter()
-                .map(|ty| CompletionItem {
+                .into_iter()
+                .enumerate()
+                .map(|(idx, (ty, suggested))| CompletionItem {
                     label: ty.name.clone(),
                     detail: Some(ty.summary.clone()),
-                    kind: Some(CompletionItemKind::TEXT),
+                    kind: Some(if complete_scope {
+                        CompletionItemKind::ENUM_MEMBER
+                    } else {
+                        CompletionItemKind::TEXT
+                    }),
                     label_details: Some(CompletionItemLabelDetails {
-                        detail: None,
+                        detail: suggested.then(|| " (staged changes)".to_owned()),
                         description: Some(ty.summary.clone()),
                     }),
                     documentation: Some(Documentation::String(ty.description.clone())),
+                    // keep our order instead of sorting alphabetically
+                    sort_text: Some(format!("{idx:04}")),
+                    preselect: suggested.then_some(true),
                     ..Default::default()
                 })
                 .collect();
@@ -203,20 +737,33 @@ impl LanguageServer for Backend {
         let Some(remote) = &self.tracker else {
             return Ok(None);
         };
-        let items: Vec<_> = remote
-            .list_tickets()
+        let query = self
+            .documents
+            .with(uri, |analysis| analysis.ticket_query(pos))
+            .flatten()
+            .unwrap_or_default();
+        let items: Vec<_> = filter_tickets(remote.list_tickets(), &query)
             .iter()
-            .map(|ticket| {
+            .enumerate()
+            .map(|(idx, ticket)| {
                 let short_title = ticket.title().truncate_ellipse_with(20, "…");
                 CompletionItem {
-                    label: format!("#{}", ticket.id()),
-                    detail: Some(ticket.title().to_owned()),
+                    label: remote.reference(ticket.id()),
+                    detail: Some(format!("[{}] {}", ticket.status(), ticket.title())),
                     kind: Some(CompletionItemKind::REFERENCE),
                     label_details: Some(CompletionItemLabelDetails {
                         detail: None,
                         description: Some(short_title.into()),
                     }),
-                    documentation: Some(Documentation::String(ticket.text().to_owned())),
+                    // allow the client to match the title as well
+                    filter_text: Some(format!(
+                        "{} {}",
+                        remote.reference(ticket.id()),
+                        ticket.title()
+                    )),
+                    sort_text: Some(format!("{idx:04}")),
+                    // the description is added in `completion_resolve`
+                    data: Some(ticket.id().into()),
                     ..Default::default()
                 }
             })
@@ -230,14 +777,611 @@ impl LanguageServer for Backend {
     }
 }
 
-pub async fn run_stdio(analysis: analysis::State, remote: Option<IssueTracker>) {
+impl Backend {
+    /// Read the repository configuration again and check the open documents with it.
+    ///
+    /// An invalid configuration is reported to the user and the previous one is kept.
+    async fn reload_config(&self) {
+        let config = match config::Repository::reload(self.config_path.as_deref()) {
+            Ok(config) => config.with_user_definitions(&self.user_config),
+            Err(e) => {
+                warn!("{e}");
+                self.client.show_message(MessageType::ERROR, e).await;
+                return;
+            }
+        };
+
+        for uri in self.documents.set_config(config) {
+            let diags = self.documents.with(&uri, |analysis| {
+                diagnostics(analysis, self.tracker.as_deref())
+            });
+            if let Some(diags) = diags {
+                self.client.publish_diagnostics(uri, diags, None).await;
+            }
+        }
+    }
+
+    /// Request the referenced tickets from upstream, once the user stops typing.
+    ///
+    /// Afterwards the inlay hints are refreshed and the diagnostics for tickets that do not
+    /// exist are published.
+    fn fetch_references(&self, uri: Url, references: Vec<u64>) {
+        let Some(tracker) = &self.tracker else {
+            return;
+        };
+        if references.is_empty() {
+            return;
+        }
+
+        // Wait until the user stops typing before asking upstream about the references,
+        // otherwise typing `#123` would query `#1`, `#12` and `#123`.
+        let tracker = tracker.clone();
+        let documents = self.documents.clone();
+        let client = self.client.clone();
+        let refresh_hints = self.ticket_inlay_hints;
+        self.fetch_debouncer.schedule(uri.clone(), async move {
+            for id in &references {
+                let _ = tracker.get_ticket_details(*id).await;
+            }
+            if refresh_hints {
+                // show the titles of the newly fetched tickets
+                let _ = client.inlay_hint_refresh().await;
+            }
+            let _ = client.code_lens_refresh().await;
+            if references.iter().any(|&id| tracker.is_missing(id)) {
+                let diags = documents.with(&uri, |analysis| diagnostics(analysis, Some(&tracker)));
+                if let Some(diags) = diags {
+                    client.publish_diagnostics(uri, diags, None).await;
+                }
+            }
+        });
+    }
+}
+
+/// A hover with Markdown content, so clients render headings and paragraphs.
+fn markdown_hover(value: String, range: Range) -> Hover {
+    Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: Some(range),
+    }
+}
+
+/// The diagnostics of the commit message, including references to tickets that the issue
+/// tracker does not know.
+fn diagnostics(
+    analysis: &analysis::State,
+    tracker: Option<&IssueTracker>,
+) -> Vec<tower_lsp::lsp_types::Diagnostic> {
+    let mut diagnostics = analysis.all_diagnostics();
+    if let Some(tracker) = tracker {
+        diagnostics.extend(analysis.check_references_exist(|id| tracker.is_missing(id)));
+    }
+    diagnostics.into_iter().map(Into::into).collect()
+}
+
+/// Count characters if the client supports it, otherwise the UTF-16 code units of the protocol.
+fn negotiate_position_encoding(capabilities: &ClientCapabilities) -> PositionEncoding {
+    let supports_utf32 = capabilities
+        .general
+        .as_ref()
+        .and_then(|general| general.position_encodings.as_ref())
+        .is_some_and(|encodings| encodings.contains(&PositionEncodingKind::UTF32));
+    if supports_utf32 {
+        PositionEncoding::Utf32
+    } else {
+        PositionEncoding::Utf16
+    }
+}
+
+/// The server information and the features advertised to the client.
+///
+/// Some features are only available if an issue tracker is connected.
+pub fn initialize_result(
+    has_tracker: bool,
+    ticket_inlay_hints: bool,
+    position_encoding: PositionEncoding,
+) -> InitializeResult {
+    InitializeResult {
+        capabilities: ServerCapabilities {
+            position_encoding: Some(position_encoding.into()),
+            text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                TextDocumentSyncKind::INCREMENTAL,
+            )),
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            inlay_hint_provider: Some(OneOf::Left(ticket_inlay_hints && has_tracker)),
+            document_symbol_provider: Some(OneOf::Left(true)),
+            document_formatting_provider: Some(OneOf::Left(true)),
+            semantic_tokens_provider: Some(
+                SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+                    legend: SemanticTokensLegend {
+                        token_types: TOKEN_TYPES.to_vec(),
+                        token_modifiers: Vec::new(),
+                    },
+                    full: Some(SemanticTokensFullOptions::Bool(true)),
+                    ..Default::default()
+                }),
+            ),
+            code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+            code_lens_provider: has_tracker.then_some(CodeLensOptions {
+                resolve_provider: Some(false),
+            }),
+            definition_provider: Some(OneOf::Left(has_tracker)),
+            rename_provider: Some(OneOf::Left(true)),
+            completion_provider: Some(tower_lsp::lsp_types::CompletionOptions {
+                resolve_provider: Some(true),
+                trigger_characters: Some(vec!["#".to_owned(), "(".to_owned()]),
+                all_commit_characters: None,
+                work_done_progress_options: WorkDoneProgressOptions {
+                    work_done_progress: None,
+                },
+                completion_item: None,
+            }),
+            ..Default::default()
+        },
+        server_info: Some(ServerInfo {
+            name: "commit-lsp".to_owned(),
+            version: Some(env!("CARGO_PKG_VERSION").to_owned()),
+        }),
+    }
+}
+
+/// Encode the tokens relative to their predecessor, as required by the protocol.
+fn encode_semantic_tokens(tokens: &[(Range, SemanticTokenType)]) -> Vec<SemanticToken> {
+    let mut previous = Position::new(0, 0);
+    tokens
+        .iter()
+        .filter_map(|(range, ty)| {
+            let token_type = TOKEN_TYPES.iter().position(|t| t == ty)? as u32;
+            let delta_line = range.start.line - previous.line;
+            let delta_start = if delta_line == 0 {
+                range.start.character - previous.character
+            } else {
+                range.start.character
+            };
+            previous = range.start;
+            Some(SemanticToken {
+                delta_line,
+                delta_start,
+                length: range.end.character - range.start.character,
+                token_type,
+                token_modifiers_bitset: 0,
+            })
+        })
+        .collect()
+}
+
+/// Describe the referenced ticket, or why it could not be retrieved.
+async fn ticket_hover_text(tracker: &IssueTracker, reference: &Reference) -> String {
+    if let Some(repo) = &reference.repo {
+        return format!(
+            "{} refers to the repository `{repo}`, only tickets of this repository can be shown",
+            reference.text
+        );
+    }
+    match tracker.get_referenced_ticket(reference).await {
+        Ok(Some(ticket)) => ticket_markdown(&ticket),
+        Ok(None) => format!("{} not found!", reference.text),
+        Err(e) => {
+            warn!("Failed to retrieve ticket {}: {e}", reference.text);
+            format!("Failed to fetch {}: {e}", reference.text)
+        }
+    }
+}
+
+fn ticket_markdown(ticket: &Ticket) -> String {
+    format!(
+        "# {}\n\n`{}`\n\n{}",
+        ticket.title(),
+        ticket.status(),
+        ticket.text()
+    )
+}
+
+/// The tickets matching the text typed after `#`: tickets whose ID starts with the query
+/// first, then tickets whose title contains it. An empty query matches all tickets.
+fn filter_tickets(tickets: Vec<Ticket>, query: &str) -> Vec<Ticket> {
+    if query.is_empty() {
+        return tickets;
+    }
+
+    let query = query.to_lowercase();
+    let (mut by_id, mut by_title): (Vec<_>, Vec<_>) = tickets
+        .into_iter()
+        .filter(|t| {
+            t.id().to_string().starts_with(&query) || t.title().to_lowercase().contains(&query)
+        })
+        .partition(|t| t.id().to_string().starts_with(&query));
+
+    by_id.sort_by_key(|t| t.id());
+    by_id.append(&mut by_title);
+    by_id
+}
+
+/// Count the tickets per status, e.g. `2 open, 1 closed`.
+/// Returns `None` if there are no tickets.
+fn ticket_status_summary(statuses: &[TicketStatus]) -> Option<String> {
+    let parts: Vec<_> = [
+        TicketStatus::Open,
+        TicketStatus::InProgress,
+        TicketStatus::Closed,
+        TicketStatus::Unknown,
+    ]
+    .into_iter()
+    .filter_map(|status| {
+        let count = statuses.iter().filter(|&&s| s == status).count();
+        (count > 0).then(|| format!("{count} {status}"))
+    })
+    .collect();
+
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
+/// Explanation of well known git trailers.
+fn trailer_documentation(key: &str) -> Option<&'static str> {
+    let help = match key.to_lowercase().as_str() {
+        "signed-off-by" => {
+            "Certifies that the author has the right to submit the change under the project's \
+             license (Developer Certificate of Origin)."
+        }
+        "co-authored-by" => "Credits an additional author of the change.",
+        "reviewed-by" => "The change was reviewed by the given person.",
+        "acked-by" => "The given person approved the change without a full review.",
+        "tested-by" => "The change was tested by the given person.",
+        "reported-by" => "The problem fixed by this change was reported by the given person.",
+        "suggested-by" => "The change was suggested by the given person.",
+        "breaking change" | "breaking-change" => "Describes a breaking change.",
+        "refs" | "closes" | "fixes" => "Links the change to a ticket.",
+        _ => return None,
+    };
+
+    Some(help)
+}
+
+/// The emoji, shortcode and meaning of common gitmojis (see <https://gitmoji.dev>).
+fn gitmoji_documentation(gitmoji: &str) -> Option<(&'static str, &'static str, &'static str)> {
+    const GITMOJIS: &[(&str, &str, &str)] = &[
+        ("🎨", ":art:", "Improve structure / format of the code."),
+        ("⚡️", ":zap:", "Improve performance."),
+        ("🔥", ":fire:", "Remove code or files."),
+        ("🐛", ":bug:", "Fix a bug."),
+        ("🚑️", ":ambulance:", "Critical hotfix."),
+        ("✨", ":sparkles:", "Introduce new features."),
+        ("📝", ":memo:", "Add or update documentation."),
+        ("🚀", ":rocket:", "Deploy stuff."),
+        ("💄", ":lipstick:", "Add or update the UI and style files."),
+        ("🎉", ":tada:", "Begin a project."),
+        ("✅", ":white_check_mark:", "Add, update, or pass tests."),
+        ("🔒️", ":lock:", "Fix security or privacy issues."),
+        ("🔖", ":bookmark:", "Release / Version tags."),
+        ("🚨", ":rotating_light:", "Fix compiler / linter warnings."),
+        ("🚧", ":construction:", "Work in progress."),
+        ("💚", ":green_heart:", "Fix CI Build."),
+        ("⬆️", ":arrow_up:", "Upgrade dependencies."),
+        ("⬇️", ":arrow_down:", "Downgrade dependencies."),
+        (
+            "👷",
+            ":construction_worker:",
+            "Add or update CI build system.",
+        ),
+        ("♻️", ":recycle:", "Refactor code."),
+        ("➕", ":heavy_plus_sign:", "Add a dependency."),
+        ("➖", ":heavy_minus_sign:", "Remove a dependency."),
+        ("🔧", ":wrench:", "Add or update configuration files."),
+        (
+            "🌐",
+            ":globe_with_meridians:",
+            "Internationalization and localization.",
+        ),
+        ("✏️", ":pencil2:", "Fix typos."),
+        ("⏪️", ":rewind:", "Revert changes."),
+        ("💥", ":boom:", "Introduce breaking changes."),
+    ];
+
+    // the variation selector is optional, `⚡` and `⚡️` are the same gitmoji
+    let unqualified = |emoji: &str| emoji.replace('\u{FE0F}', "");
+    GITMOJIS
+        .iter()
+        .find(|(emoji, code, _)| unqualified(emoji) == unqualified(gitmoji) || *code == gitmoji)
+        .copied()
+}
+
+pub async fn run_stdio(
+    analysis: analysis::State,
+    remote: Option<IssueTracker>,
+    config: &config::User,
+    config_path: Option<&Path>,
+) {
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
     let (service, socket) = LspService::new(|client| Backend {
         client,
-        analysis: analysis.into(),
+        documents: Documents::new(analysis),
         tracker: remote.map(Arc::new),
+        fetch_debouncer: Debouncer::new(config.fetch_cooldown()),
+        diagnostics_debouncer: Debouncer::new(config.diagnostics_cooldown()),
+        ticket_inlay_hints: config.ticket_inlay_hints(),
+        // the client watches the file by an absolute path
+        config_path: config_path.map(|p| std::path::absolute(p).unwrap_or_else(|_| p.to_owned())),
+        user_config: config.clone(),
     });
     Server::new(stdin, stdout, socket).serve(service).await;
 }
+
+#[cfg(test)]
+mod test {
+    use async_trait::async_trait;
+
+    use super::*;
+    use commit_lsp::issue_tracker::{IssueTrackerAdapter, Ticket, UpstreamError};
+    use tower_lsp::lsp_types::GeneralClientCapabilities;
+
+    /// Knows a single ticket `#1`, fails for all others.
+    struct StubAdapter;
+
+    #[async_trait]
+    impl IssueTrackerAdapter for StubAdapter {
+        async fn list_ticket_numbers(&self) -> std::result::Result<Vec<u64>, UpstreamError> {
+            Ok(vec![1])
+        }
+
+        async fn get_ticket_details(
+            &self,
+            ids: &[u64],
+        ) -> std::result::Result<Vec<Ticket>, UpstreamError> {
+            if ids == [1] {
+                Ok(vec![Ticket::new(1, "Title".to_owned(), "Text".to_owned())])
+            } else {
+                Err(UpstreamError::Request("connection refused".into()))
+            }
+        }
+
+        fn web_url(&self, _id: u64) -> Option<String> {
+            None
+        }
+    }
+
+    fn reference(id: u64) -> Reference {
+        Reference {
+            text: format!("#{id}"),
+            key: id.to_string(),
+            range: Range::new(Position::new(0, 0), Position::new(0, 2)),
+            closing: false,
+            repo: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_debouncer_runs_latest_action() {
+        let debouncer = Debouncer::new(Duration::from_millis(20));
+        let runs = Arc::new(Mutex::new(Vec::new()));
+
+        let uri = Url::parse("file:///COMMIT_EDITMSG").unwrap();
+        for i in 0..3 {
+            let runs = runs.clone();
+            debouncer.schedule(uri.clone(), async move { runs.lock().unwrap().push(i) });
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(*runs.lock().unwrap(), [2]);
+    }
+
+    #[tokio::test]
+    async fn test_debouncer_is_per_document() {
+        let debouncer = Debouncer::new(Duration::from_millis(20));
+        let runs = Arc::new(Mutex::new(Vec::new()));
+
+        for name in ["a", "b", "a", "b"] {
+            let uri = Url::parse(&format!("file:///{name}/COMMIT_EDITMSG")).unwrap();
+            let runs = runs.clone();
+            debouncer.schedule(uri, async move { runs.lock().unwrap().push(name) });
+        }
+        let closed = Url::parse("file:///c/COMMIT_EDITMSG").unwrap();
+        let runs_c = runs.clone();
+        debouncer.schedule(
+            closed.clone(),
+            async move { runs_c.lock().unwrap().push("c") },
+        );
+        debouncer.forget(&closed);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut runs = runs.lock().unwrap().clone();
+        runs.sort();
+        assert_eq!(runs, ["a", "b"]);
+    }
+
+    #[test]
+    fn test_initialize_result() {
+        let result =
+            serde_json::to_value(initialize_result(false, true, PositionEncoding::Utf16)).unwrap();
+
+        assert_eq!(result["serverInfo"]["name"], "commit-lsp");
+        assert_eq!(result["serverInfo"]["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(result["capabilities"]["hoverProvider"], true);
+        assert_eq!(result["capabilities"]["inlayHintProvider"], false);
+        assert_eq!(result["capabilities"]["definitionProvider"], false);
+        assert_eq!(result["capabilities"]["positionEncoding"], "utf-16");
+
+        let result =
+            serde_json::to_value(initialize_result(true, true, PositionEncoding::Utf32)).unwrap();
+        assert_eq!(result["capabilities"]["inlayHintProvider"], true);
+        assert_eq!(result["capabilities"]["positionEncoding"], "utf-32");
+    }
+
+    #[test]
+    fn test_negotiate_position_encoding() {
+        let client = |encodings: Option<Vec<PositionEncodingKind>>| ClientCapabilities {
+            general: Some(GeneralClientCapabilities {
+                position_encodings: encodings,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            negotiate_position_encoding(&ClientCapabilities::default()),
+            PositionEncoding::Utf16
+        );
+        assert_eq!(
+            negotiate_position_encoding(&client(None)),
+            PositionEncoding::Utf16
+        );
+        assert_eq!(
+            negotiate_position_encoding(&client(Some(vec![
+                PositionEncodingKind::UTF8,
+                PositionEncodingKind::UTF16
+            ]))),
+            PositionEncoding::Utf16
+        );
+        assert_eq!(
+            negotiate_position_encoding(&client(Some(vec![
+                PositionEncodingKind::UTF16,
+                PositionEncodingKind::UTF32
+            ]))),
+            PositionEncoding::Utf32
+        );
+    }
+
+    fn ticket_ids(tickets: &[(u64, &str)], query: &str) -> Vec<u64> {
+        let tickets = tickets
+            .iter()
+            .map(|&(id, title)| Ticket::new(id, title.to_owned(), String::new()))
+            .collect();
+        filter_tickets(tickets, query)
+            .iter()
+            .map(Ticket::id)
+            .collect()
+    }
+
+    #[test]
+    fn test_filter_tickets() {
+        let tickets = [
+            (7, "Release 12.0"),
+            (120, "Crash on start"),
+            (12, "Login fails"),
+            (312, "Update docs"),
+        ];
+
+        assert_eq!(ticket_ids(&tickets, ""), [7, 120, 12, 312]);
+        assert_eq!(ticket_ids(&tickets, "12"), [12, 120, 7]);
+        assert_eq!(ticket_ids(&tickets, "3"), [312]);
+        assert_eq!(ticket_ids(&tickets, "login"), [12]);
+        assert!(ticket_ids(&tickets, "99").is_empty());
+    }
+
+    #[test]
+    fn test_ticket_status_summary() {
+        use TicketStatus::*;
+
+        assert_eq!(
+            ticket_status_summary(&[Closed, Open, Open]).as_deref(),
+            Some("2 open, 1 closed")
+        );
+        assert_eq!(
+            ticket_status_summary(&[InProgress, Unknown]).as_deref(),
+            Some("1 in progress, 1 unknown")
+        );
+        assert_eq!(ticket_status_summary(&[]), None);
+    }
+
+    #[test]
+    fn test_encode_semantic_tokens() {
+        let tokens = [
+            (
+                Range::new(Position::new(0, 0), Position::new(0, 4)),
+                SemanticTokenType::KEYWORD,
+            ),
+            (
+                Range::new(Position::new(0, 6), Position::new(0, 10)),
+                SemanticTokenType::STRING,
+            ),
+            (
+                Range::new(Position::new(2, 6), Position::new(2, 8)),
+                SemanticTokenType::NUMBER,
+            ),
+        ];
+
+        let encoded: Vec<_> = encode_semantic_tokens(&tokens)
+            .into_iter()
+            .map(|t| (t.delta_line, t.delta_start, t.length, t.token_type))
+            .collect();
+
+        assert_eq!(encoded, [(0, 0, 4, 0), (0, 6, 4, 3), (2, 6, 2, 4)]);
+    }
+
+    #[test]
+    fn test_documents_are_analysed_separately() {
+        let documents = Documents::new(analysis::State::new(Default::default()));
+        let first = Url::parse("file:///repo/.git/COMMIT_EDITMSG").unwrap();
+        let second = Url::parse("file:///other/.git/COMMIT_EDITMSG").unwrap();
+
+        documents.open(first.clone(), "feat: first #1");
+        documents.open(second.clone(), "fix: second #2\n\nRefs #3");
+
+        let references = |uri| documents.with(uri, |analysis| analysis.referenced_tickets());
+        assert_eq!(references(&first), Some(vec![1]));
+        assert_eq!(references(&second), Some(vec![2, 3]));
+
+        documents.with(&first, |analysis| {
+            analysis.update_text("feat: changed #4");
+        });
+        assert_eq!(references(&first), Some(vec![4]));
+        assert_eq!(references(&second), Some(vec![2, 3]));
+
+        assert!(documents.close(&first));
+        assert!(!documents.close(&first));
+        assert_eq!(references(&first), None);
+        assert_eq!(references(&second), Some(vec![2, 3]));
+    }
+
+    #[test]
+    fn test_gitmoji_documentation() {
+        let (emoji, code, _) = gitmoji_documentation(":sparkles:").unwrap();
+        assert_eq!((emoji, code), ("✨", ":sparkles:"));
+        // with and without variation selector
+        assert_eq!(gitmoji_documentation("⚡").unwrap().1, ":zap:");
+        assert_eq!(gitmoji_documentation("⚡️").unwrap().1, ":zap:");
+        assert!(gitmoji_documentation(":unknown:").is_none());
+    }
+
+    #[test]
+    fn test_hover_is_markdown() {
+        let range = Range::new(Position::new(0, 0), Position::new(0, 4));
+        let hover = markdown_hover("# Title\n\nText".to_owned(), range);
+
+        let HoverContents::Markup(content) = hover.contents else {
+            panic!("expected markup content, got {:?}", hover.contents);
+        };
+        assert_eq!(content.kind, MarkupKind::Markdown);
+        assert_eq!(content.value, "# Title\n\nText");
+        assert_eq!(hover.range, Some(range));
+    }
+
+    #[tokio::test]
+    async fn test_ticket_hover_text() {
+        let tracker = IssueTracker::from_adapter(Box::new(StubAdapter));
+
+        assert_eq!(
+            ticket_hover_text(&tracker, &reference(1)).await,
+            "# Title\n\n`unknown`\n\nText"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ticket_hover_text_upstream_error() {
+        let tracker = IssueTracker::from_adapter(Box::new(StubAdapter));
+
+        assert_eq!(
+            ticket_hover_text(&tracker, &reference(42)).await,
+            "Failed to fetch #42: Request to issue tracker failed: connection refused"
+        );
+        // the tracker keeps working after an error
+        assert_eq!(
+            ticket_hover_text(&tracker, &reference(1)).await,
+            "# Title\n\n`unknown`\n\nText"
+        );
+    }
+}
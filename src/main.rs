@@ -1,64 +1,161 @@
-use std::{fs::File, io::Read, process::ExitCode, sync::Mutex};
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Mutex,
+};
 
 use clap::Parser as _;
-use cli::Cli;
-use git::guess_repo_url;
-use healthcheck::HealthReport;
-use issue_tracker::IssueTracker;
-use tracing::{info, trace};
+use cli::{Cli, OutputFormat};
+use commit_lsp::healthcheck::HealthReport;
+use commit_lsp::{analysis, config, git};
+use tracing_subscriber::{fmt::writer::BoxMakeWriter, EnvFilter};
+#[cfg(feature = "issue-tracker")]
+use {
+    commit_lsp::{git::guess_repo_url, issue_tracker::IssueTracker, text_util::Ellipse as _},
+    git_url_parse::GitUrl,
+    tracing::{info, trace},
+};
 
-pub mod analysis;
 mod cli;
-pub mod issue_tracker;
+#[cfg(feature = "issue-tracker")]
 mod lsp;
 
-pub mod healthcheck;
-
-pub mod config;
-
-pub mod git;
-
-pub mod text_util;
-
-#[tokio::main]
-async fn main() -> ExitCode {
+fn main() -> ExitCode {
     let cli = Cli::parse();
 
-    if cfg!(debug_assertions) {
-        let log_file = File::create("commit-lsp.log").expect("Failed to create log file");
-        let subscriber = tracing_subscriber::fmt()
-            .without_time()
-            .pretty()
-            .with_writer(Mutex::new(log_file))
-            .finish();
-        tracing::subscriber::set_global_default(subscriber).unwrap();
+    init_logging();
+
+    let config_path = cli.config.as_deref();
+    if let Some(path) = config_path.filter(|p| !p.exists()) {
+        eprintln!("Config file '{}' does not exist", path.display());
+        return ExitCode::FAILURE;
     }
 
     match cli.action {
-        cli::Action::Run => {
+        cli::Action::Lint {
+            file,
+            fail_on,
+            format,
+        } => {
+            let mut health = HealthReport::silent();
+            let mut text = String::new();
+            if file.as_os_str() == "-" {
+                std::io::stdin().read_to_string(&mut text).unwrap();
+            } else {
+                File::open(&file)
+                    .unwrap()
+                    .read_to_string(&mut text)
+                    .unwrap();
+            }
+            let user_config = config::User::load_default_file(&mut health);
+            let mut repo_config =
+                load_repo_config(config_path, &mut health).with_user_definitions(&user_config);
+            if let Some(fail_on) = fail_on {
+                repo_config.fail_on = fail_on;
+            }
+            analyse_commit(repo_config, &text, format)
+        }
+        cli::Action::Init { user, force } => init_config(user, force),
+        cli::Action::CheckConfig => check_config(config_path),
+        #[cfg(feature = "issue-tracker")]
+        action => run_with_issue_tracker(action, config_path),
+    }
+}
+
+/// Run the actions that need the issue tracker (and with it the async runtime).
+#[cfg(feature = "issue-tracker")]
+#[tokio::main]
+async fn run_with_issue_tracker(action: cli::Action, config_path: Option<&Path>) -> ExitCode {
+    match action {
+        cli::Action::Run { since } => {
             let mut health = HealthReport::silent();
             let user_config = config::User::load_default_file(&mut health);
-            let repo_config = config::Repository::load_default_file(&mut health);
+            let repo_config = load_repo_config(config_path, &mut health);
             trace!("Using config: {:?}", repo_config);
-            let remote = initialize_issue_tracker(&user_config, &mut health);
-            let analysis = analysis::State::new(repo_config);
-            lsp::run_stdio(analysis, remote).await;
+            let mut remote = initialize_issue_tracker(&user_config, &repo_config, &mut health);
+            if let Some(remote) = &mut remote {
+                remote.set_updated_within(since);
+            }
+            let mut analysis =
+                analysis::State::new(repo_config.with_user_definitions(&user_config));
+            analysis.set_staged_files(git::get_staged_files());
+            lsp::run_stdio(analysis, remote, &user_config, config_path).await;
         }
-        cli::Action::Lint { file } => {
+        cli::Action::Tickets { json, since } => {
             let mut health = HealthReport::silent();
-            let mut text = String::new();
-            File::open(&file)
-                .unwrap()
-                .read_to_string(&mut text)
-                .unwrap();
-            let repo_config = config::Repository::load_default_file(&mut health);
-            return analyse_commit(repo_config, &text);
+            let user_config = config::User::load_default_file(&mut health);
+            let repo_config = load_repo_config(config_path, &mut health);
+            let Some(mut remote) =
+                initialize_issue_tracker(&user_config, &repo_config, &mut health)
+            else {
+                eprintln!(
+                    "No issue tracker configured for this repository, see `commit-lsp checkhealth`"
+                );
+                return ExitCode::FAILURE;
+            };
+            remote.set_updated_within(since);
+
+            let tickets = match remote.request_ticket_information().await {
+                Ok(tickets) => tickets
+                    .into_iter()
+                    .filter(|t| remote.is_updated_within(t))
+                    .collect::<Vec<_>>(),
+                Err(e) => {
+                    eprintln!("{e}");
+                    return ExitCode::FAILURE;
+                }
+            };
+
+            if json {
+                let tickets: Vec<_> = tickets
+                    .iter()
+                    .map(|t| {
+                        serde_json::json!({
+                            "id": t.id(),
+                            "title": t.title(),
+                            "status": t.status().to_string(),
+                        })
+                    })
+                    .collect();
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&tickets).expect("Tickets are serializable")
+                );
+            } else {
+                for ticket in tickets {
+                    println!(
+                        "{} {}",
+                        remote.reference(ticket.id()),
+                        ticket.title().truncate_ellipse(60)
+                    );
+                }
+            }
+        }
+        cli::Action::Capabilities => {
+            let mut health = HealthReport::silent();
+            let user_config = config::User::load_default_file(&mut health);
+            let repo_config = load_repo_config(config_path, &mut health);
+            let remote = initialize_issue_tracker(&user_config, &repo_config, &mut health);
+
+            // without a client to negotiate with, positions use the default of the protocol
+            let result = lsp::initialize_result(
+                remote.is_some(),
+                user_config.ticket_inlay_hints(),
+                commit_lsp::analysis::PositionEncoding::Utf16,
+            );
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&result).expect("Capabilities are serializable")
+            );
         }
         cli::Action::Checkhealth => {
             let mut health = HealthReport::new("commit-lsp");
             let user_config = config::User::load_default_file(&mut health);
-            let _repo_config = config::Repository::load_default_file(&mut health);
-            let remote = initialize_issue_tracker(&user_config, &mut health);
+            let repo_config = load_repo_config(config_path, &mut health);
+            check_reference_pattern(&repo_config, &mut health);
+            let remote = initialize_issue_tracker(&user_config, &repo_config, &mut health);
 
             if let Some(remote) = remote {
                 let check = health.start("request tickets");
@@ -66,8 +163,8 @@ async fn main() -> ExitCode {
                     Ok(tickets) if !tickets.is_empty() => {
                         let example = tickets.first().unwrap();
                         check.ok_with(format!(
-                            "Example ticket: #{} '{}'",
-                            example.id(),
+                            "Example ticket: {} '{}'",
+                            remote.reference(example.id()),
                             example.title()
                         ));
                     }
@@ -80,41 +177,283 @@ async fn main() -> ExitCode {
                 }
             }
         }
+        cli::Action::Lint { .. } | cli::Action::Init { .. } | cli::Action::CheckConfig => {
+            unreachable!("handled without the issue tracker")
+        }
     }
 
     ExitCode::SUCCESS
 }
 
-fn analyse_commit(config: config::Repository, text: &str) -> ExitCode {
+/// Where to log to and what.
+#[derive(Debug, PartialEq)]
+struct LogSettings {
+    filter: String,
+    /// Log to stderr if not set.
+    file: Option<PathBuf>,
+}
+
+impl LogSettings {
+    /// Decide from the environment variables `COMMIT_LSP_LOG` (or `RUST_LOG`), which contains
+    /// the filter (like `debug` or `commit_lsp=trace`), and `COMMIT_LSP_LOG_FILE`.
+    ///
+    /// Returns `None` if nothing should be logged: release builds are quiet unless one of the
+    /// variables is set, debug builds log to `commit-lsp.log` by default.
+    fn from_env(env: impl Fn(&str) -> Option<String>, debug_build: bool) -> Option<Self> {
+        let filter = env("COMMIT_LSP_LOG").or_else(|| env("RUST_LOG"));
+        let file = env("COMMIT_LSP_LOG_FILE").map(PathBuf::from);
+        if filter.is_none() && file.is_none() && !debug_build {
+            return None;
+        }
+
+        Some(Self {
+            filter: filter.unwrap_or_else(|| "info".to_owned()),
+            file: file.or_else(|| debug_build.then(|| PathBuf::from("commit-lsp.log"))),
+        })
+    }
+}
+
+/// Set up logging according to [`LogSettings::from_env`].
+///
+/// Never logs to stdout, which carries the language server protocol.
+fn init_logging() {
+    let Some(settings) =
+        LogSettings::from_env(|var| std::env::var(var).ok(), cfg!(debug_assertions))
+    else {
+        return;
+    };
+
+    let filter = EnvFilter::try_new(&settings.filter).unwrap_or_else(|e| {
+        eprintln!("Invalid log filter '{}': {e}", settings.filter);
+        EnvFilter::new("info")
+    });
+    let writer = match &settings.file {
+        Some(path) => match File::create(path) {
+            Ok(file) => BoxMakeWriter::new(Mutex::new(file)),
+            Err(e) => {
+                eprintln!("Failed to create log file '{}': {e}", path.display());
+                return;
+            }
+        },
+        None => BoxMakeWriter::new(std::io::stderr),
+    };
+
+    let subscriber = tracing_subscriber::fmt()
+        .without_time()
+        .pretty()
+        .with_ansi(settings.file.is_none())
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .finish();
+    tracing::subscriber::set_global_default(subscriber).unwrap();
+}
+
+/// Load the repository configuration from `config_path`, or from the root of the repository.
+fn load_repo_config(config_path: Option<&Path>, health: &mut HealthReport) -> config::Repository {
+    match config_path {
+        Some(path) => config::Repository::load_from_path(path, health),
+        None => config::Repository::load_default_file(health),
+    }
+}
+
+fn init_config(user: bool, force: bool) -> ExitCode {
+    let Some(repo_root) = git::get_repo_root() else {
+        eprintln!("Not inside a git repository");
+        return ExitCode::FAILURE;
+    };
+
+    let mut files = vec![(
+        repo_root.join(".commit-lsp.toml"),
+        config::Repository::EXAMPLE,
+    )];
+    if user {
+        files.push((config::User::default_path(), config::User::EXAMPLE));
+    }
+
+    let mut result = ExitCode::SUCCESS;
+    for (path, content) in files {
+        if path.exists() && !force {
+            eprintln!(
+                "'{}' already exists, use --force to overwrite it",
+                path.display()
+            );
+            result = ExitCode::FAILURE;
+            continue;
+        }
+
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&path, content));
+        match written {
+            Ok(()) => println!("Wrote '{}'", path.display()),
+            Err(e) => {
+                eprintln!("Failed to write '{}': {e}", path.display());
+                result = ExitCode::FAILURE;
+            }
+        }
+    }
+
+    result
+}
+
+fn analyse_commit(config: config::Repository, text: &str, format: OutputFormat) -> ExitCode {
+    let fail_on = config.fail_on;
     let mut state = analysis::State::new(config);
+    state.set_staged_files(git::get_staged_files());
     state.update_text(text);
     let diagnostics = state.all_diagnostics();
 
-    for diag in &diagnostics {
-        println!("{}", diag);
+    match format {
+        OutputFormat::Text => {
+            for diag in &diagnostics {
+                println!("{}", diag);
+            }
+        }
+        OutputFormat::Json => {
+            let reports: Vec<_> = diagnostics.iter().map(|d| d.report()).collect();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&reports).expect("Diagnostics are serializable")
+            );
+        }
     }
 
-    if diagnostics.is_empty() {
-        ExitCode::SUCCESS
+    if diagnostics.iter().any(|d| d.fails(fail_on)) {
+        ExitCode::FAILURE
     } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Check that both configuration files parse and summarize what they configure.
+fn check_config(config_path: Option<&Path>) -> ExitCode {
+    let mut health = HealthReport::new("commit-lsp");
+    let user_config = config::User::load_default_file(&mut health);
+    let repo_config = load_repo_config(config_path, &mut health);
+    check_reference_pattern(&repo_config, &mut health);
+
+    health.set_context("Summary");
+    let merged = repo_config.clone().with_user_definitions(&user_config);
+    let count = |total: usize, repo: usize| format!("{total} ({} personal)", total - repo);
+    health
+        .start("commit types")
+        .ok_with(count(merged.types.len(), repo_config.types.len()));
+    health
+        .start("scopes")
+        .ok_with(count(merged.scopes.len(), repo_config.scopes.len()));
+    for remote in &user_config.remotes {
+        let tracker = match remote.issue_tracker_type {
+            Some(ty) => format!("{ty:?}"),
+            None => "detected from the host".to_owned(),
+        };
+        health
+            .start(format!("remote '{}'", remote.host))
+            .ok_with(format!("Issue tracker: {tracker}"));
+    }
+
+    if health.has_errors() {
         ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
     }
 }
 
+/// Check that the configured reference pattern compiles and show what it finds in a sample.
+fn check_reference_pattern(repo_config: &config::Repository, health: &mut HealthReport) {
+    const SAMPLE: &str = "Refs #123, PROJ-123";
+
+    health.set_context("Reference Pattern");
+    let check = match &repo_config.issue_reference_pattern {
+        Some(pattern) => health.start(format!("compile issue_reference_pattern '{pattern}'")),
+        None => health.start(format!(
+            "default reference pattern '{}'",
+            analysis::default_reference_pattern()
+        )),
+    };
+    match analysis::sample_reference_keys(repo_config, SAMPLE) {
+        Ok(keys) if keys.is_empty() => {
+            check.info(format!("Finds no references in '{SAMPLE}'"));
+        }
+        Ok(keys) => check.ok_with(format!("Finds the keys {keys:?} in '{SAMPLE}'")),
+        Err(e) => check.error(e.to_string()),
+    }
+}
+
+#[cfg(feature = "issue-tracker")]
 fn initialize_issue_tracker(
     config: &config::User,
+    repo_config: &config::Repository,
     health: &mut HealthReport,
 ) -> Option<IssueTracker> {
     health.set_context("Issue Tracker");
 
-    let check = health.start("retrieve repo url");
-    let url_info = guess_repo_url();
-    match &url_info {
-        Some(url) => check.ok_with(format!("Got '{url}'")),
-        None => check.error("Failed to get remote url"),
-    }
-    let url_info = url_info?;
+    let url_info = if let Some(url) = &repo_config.issue_tracker_url {
+        let check = health.start(format!("parse configured issue_tracker_url '{url}'"));
+        match GitUrl::parse(url) {
+            Ok(url) => {
+                check.ok();
+                url
+            }
+            Err(e) => {
+                check.error(e.to_string());
+                return None;
+            }
+        }
+    } else {
+        let remote_name = repo_config.remote_name();
+        let check = health.start(format!("retrieve url of remote '{remote_name}'"));
+        let url_info = guess_repo_url(remote_name);
+        match &url_info {
+            Some(url) => check.ok_with(format!("Got '{url}'")),
+            None => check.error(format!("Failed to get url of remote '{remote_name}'")),
+        }
+        url_info?
+    };
 
     info!("Using git url '{url_info}'");
-    IssueTracker::guess_from_remote(url_info, &config, health)
+    IssueTracker::guess_from_remote(url_info, config, health)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn settings(vars: &[(&str, &str)], debug_build: bool) -> Option<LogSettings> {
+        let env = |name: &str| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        };
+        LogSettings::from_env(env, debug_build)
+    }
+
+    #[test]
+    fn log_settings() {
+        assert_eq!(settings(&[], false), None);
+        assert_eq!(
+            settings(&[], true),
+            Some(LogSettings {
+                filter: "info".to_owned(),
+                file: Some("commit-lsp.log".into()),
+            })
+        );
+        assert_eq!(
+            settings(&[("RUST_LOG", "warn"), ("COMMIT_LSP_LOG", "debug")], false),
+            Some(LogSettings {
+                filter: "debug".to_owned(),
+                file: None,
+            })
+        );
+        assert_eq!(
+            settings(
+                &[("RUST_LOG", "trace"), ("COMMIT_LSP_LOG_FILE", "/tmp/x.log")],
+                true
+            ),
+            Some(LogSettings {
+                filter: "trace".to_owned(),
+                file: Some("/tmp/x.log".into()),
+            })
+        );
+    }
 }
@@ -0,0 +1,64 @@
+//! Compiling regular expressions once and reusing them.
+//!
+//! Patterns written in the code use [`regex!`](crate::regex), patterns from the configuration
+//! (like `issue_reference_pattern`) use [`compile_user_pattern`].
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+use regex::Regex;
+
+/// Compile a regex literal on first use and return the same `&'static Regex` afterwards.
+///
+/// Panics on the first use if the literal is not a valid regex.
+#[macro_export]
+macro_rules! regex {
+    ($re:literal $(,)?) => {{
+        static RE: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+        RE.get_or_init(|| regex::Regex::new($re).unwrap())
+    }};
+}
+
+/// Compile a pattern given by the user, reusing the result for the same pattern.
+///
+/// Invalid patterns are not cached, so the error is returned on every call.
+pub fn compile_user_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    static CACHE: OnceLock<Mutex<HashMap<String, Regex>>> = OnceLock::new();
+    let mut cache = CACHE.get_or_init(Default::default).lock().unwrap();
+
+    if let Some(regex) = cache.get(pattern) {
+        // cloning shares the compiled program
+        return Ok(regex.clone());
+    }
+    let regex = Regex::new(pattern)?;
+    cache.insert(pattern.to_owned(), regex.clone());
+    Ok(regex)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn header() -> &'static Regex {
+        regex!(r"^[a-z]+: ")
+    }
+
+    #[test]
+    fn regex_is_compiled_once() {
+        assert!(std::ptr::eq(header(), header()));
+        assert!(header().is_match("feat: thing"));
+    }
+
+    #[test]
+    fn user_pattern() {
+        let first = compile_user_pattern("PROJ-([0-9]+)").unwrap();
+        let second = compile_user_pattern("PROJ-([0-9]+)").unwrap();
+        assert_eq!(first.as_str(), second.as_str());
+        assert!(first.is_match("PROJ-12"));
+
+        assert!(compile_user_pattern("PROJ-([0-9]+").is_err());
+        assert!(compile_user_pattern("PROJ-([0-9]+").is_err());
+    }
+}
@@ -1,4 +1,6 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, time::Duration};
+
+use crate::regex;
 
 /// Truncate and ellipse strings in a human-friendly way.
 pub trait Ellipse {
@@ -36,13 +38,59 @@ impl<'a> Ellipse for &'a str {
     }
 }
 
-/// Easily create lazily initialized regex objects.
-#[macro_export]
-macro_rules! regex {
-    ($re:literal $(,)?) => {{
-        static RE: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
-        RE.get_or_init(|| regex::Regex::new($re).unwrap())
-    }};
+/// Parse a human-friendly duration like `30m`, `12h`, `7d` or `2w`.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let unit_start = text
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("Missing unit in duration '{text}'"))?;
+    let (amount, unit) = text.split_at(unit_start);
+
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("Invalid amount in duration '{text}'"))?;
+
+    let seconds = match unit {
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("Unknown unit '{unit}', expected one of m, h, d, w")),
+    };
+
+    Ok(Duration::from_secs(amount * seconds))
+}
+
+/// Convert the HTML of rich text fields (e.g. Azure DevOps descriptions) to Markdown.
+///
+/// Only the basic structure is kept: line breaks, paragraphs, list items and emphasis.
+/// All other tags are dropped.
+pub fn html_to_markdown(html: &str) -> String {
+    let text = regex!(r"(?i)<br\s*/?>").replace_all(html, "\n");
+    let text = regex!(r"(?i)</(p|div|h[1-6]|ul|ol)>").replace_all(&text, "\n\n");
+    let text = regex!(r"(?i)</li>").replace_all(&text, "\n");
+    let text = regex!(r"(?i)<li(\s[^>]*)?>").replace_all(&text, "- ");
+    let text = regex!(r"(?i)</?(b|strong)>").replace_all(&text, "**");
+    let text = regex!(r"(?i)</?(i|em)>").replace_all(&text, "_");
+    let text = regex!(r"<[^>]*>").replace_all(&text, "");
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'");
+    let text = regex!(r"&#(x[0-9a-fA-F]+|[0-9]+);").replace_all(&text, |c: &regex::Captures| {
+        let code = &c[1];
+        let code = match code.strip_prefix('x') {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => code.parse().ok(),
+        };
+        code.and_then(char::from_u32)
+            .map_or_else(|| c[0].to_owned(), String::from)
+    });
+    let text = text.replace("&amp;", "&");
+    let text = regex!(r"\n{3,}").replace_all(&text, "\n\n");
+
+    text.trim().to_owned()
 }
 
 #[cfg(test)]
@@ -63,4 +111,32 @@ mod tests {
     fn truncate_to_empty() {
         assert_eq!(&"Hello, World!".truncate_ellipse(0), "");
     }
+
+    #[test]
+    fn duration_days() {
+        assert_eq!(
+            parse_duration("7d"),
+            Ok(Duration::from_secs(7 * 24 * 60 * 60))
+        );
+    }
+
+    #[test]
+    fn html_to_markdown_keeps_structure() {
+        assert_eq!(
+            html_to_markdown(
+                "<div>Fix <b>all</b> the &lt;bugs&gt;:</div><ul><li>one</li><li>two</li></ul>"
+            ),
+            "Fix **all** the <bugs>:\n\n- one\n- two"
+        );
+        assert_eq!(html_to_markdown("first<br/>second"), "first\nsecond");
+        assert_eq!(html_to_markdown("plain &amp;amp; text"), "plain &amp; text");
+        assert_eq!(html_to_markdown("&#35;1 &#x2014; done"), "#1 — done");
+    }
+
+    #[test]
+    fn duration_invalid() {
+        assert!(parse_duration("7").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("7y").is_err());
+    }
 }
@@ -0,0 +1,142 @@
+use std::{
+    io::Write as _,
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures/commits")
+        .join(name)
+}
+
+/// Run `commit-lsp lint` inside an empty git repository.
+fn lint(args: &[&str], stdin: Option<&str>) -> std::process::Output {
+    lint_in_repo(&[], args, stdin)
+}
+
+/// Run `commit-lsp lint` inside a git repository containing `files` (path and content).
+fn lint_in_repo(
+    files: &[(&str, &str)],
+    args: &[&str],
+    stdin: Option<&str>,
+) -> std::process::Output {
+    // the repository config is looked up relative to the git root
+    static RUN: AtomicUsize = AtomicUsize::new(0);
+    let run = RUN.fetch_add(1, Ordering::SeqCst);
+    let repo = std::env::temp_dir().join(format!("commit-lsp-lint-{}-{run}", std::process::id()));
+    std::fs::create_dir_all(&repo).unwrap();
+    let status = Command::new("git")
+        .args(["init", "--quiet"])
+        .current_dir(&repo)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    for (path, content) in files {
+        let path = repo.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_commit-lsp"))
+        .arg("lint")
+        .args(args)
+        .current_dir(&repo)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut input = child.stdin.take().unwrap();
+    input
+        .write_all(stdin.unwrap_or_default().as_bytes())
+        .unwrap();
+    drop(input);
+    let output = child.wait_with_output().unwrap();
+
+    std::fs::remove_dir_all(&repo).unwrap();
+    output
+}
+
+#[test]
+fn json_output() {
+    let file = fixture("invalid.txt");
+    let output = lint(&[file.to_str().unwrap(), "--format", "json"], None);
+    assert!(!output.status.success());
+
+    let diagnostics: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        diagnostics,
+        serde_json::json!([
+            {
+                "line": 2,
+                "column": 1,
+                "end_line": 2,
+                "end_column": 5,
+                "severity": "error",
+                "code": "empty-second-line",
+                "message": "The second line should be empty!",
+            },
+            {
+                "line": 1,
+                "column": 19,
+                "end_line": 1,
+                "end_column": 20,
+                "severity": "warning",
+                "code": "subject-trailing-period",
+                "message": "The subject should not end with a period",
+            },
+        ])
+    );
+}
+
+#[test]
+fn text_output() {
+    let file = fixture("invalid.txt");
+    let output = lint(&[file.to_str().unwrap()], None);
+    assert!(!output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout,
+        "[2:1] The second line should be empty! [empty-second-line]\n\
+         [1:19] The subject should not end with a period [subject-trailing-period]\n"
+    );
+}
+
+#[test]
+fn config_override() {
+    let config = std::env::temp_dir().join(format!("commit-lsp-ci-{}.toml", std::process::id()));
+    std::fs::write(&config, "types_requiring_scope = ['fix']").unwrap();
+    let path = config.to_str().unwrap();
+
+    let output = lint(&["-", "--config", path], Some("fix: thing\n"));
+    let missing = lint(
+        &["-", "--config", "does-not-exist.toml"],
+        Some("fix: thing\n"),
+    );
+    std::fs::remove_file(&config).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("[missing-scope]"), "{stdout}");
+    assert!(!missing.status.success());
+}
+
+#[test]
+fn alternative_config_file() {
+    let files = [(".config/commit-lsp.toml", "types_requiring_scope = ['fix']")];
+    let output = lint_in_repo(&files, &["-"], Some("fix: thing\n"));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("[missing-scope]"), "{stdout}");
+}
+
+#[test]
+fn stdin() {
+    let output = lint(&["-"], Some("fix: thing\n"));
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let output = lint(&["-"], Some("fix: thing\nbody\n"));
+    assert!(!output.status.success());
+}
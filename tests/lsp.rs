@@ -0,0 +1,195 @@
+use std::{
+    io::{BufRead as _, BufReader, Read as _, Write as _},
+    path::PathBuf,
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+
+/// Frame a JSON-RPC message like the language server protocol does.
+fn message(body: Value) -> Vec<u8> {
+    let body = body.to_string();
+    format!("Content-Length: {}\r\n\r\n{body}", body.len()).into_bytes()
+}
+
+/// Read the next message written by the server.
+/// Panics if anything but a framed message was written, returns `None` at the end of the output.
+fn read_message(stdout: &mut BufReader<ChildStdout>) -> Option<Value> {
+    let mut header = String::new();
+    if stdout.read_line(&mut header).unwrap() == 0 {
+        return None;
+    }
+    let length: usize = header
+        .strip_prefix("Content-Length: ")
+        .and_then(|l| l.strip_suffix("\r\n"))
+        .and_then(|l| l.parse().ok())
+        .unwrap_or_else(|| panic!("Expected a message header, got {header:?}"));
+
+    let mut separator = String::new();
+    stdout.read_line(&mut separator).unwrap();
+    assert_eq!(separator, "\r\n");
+
+    let mut body = vec![0; length];
+    stdout.read_exact(&mut body).unwrap();
+    let message: Value = serde_json::from_slice(&body).expect("Message is JSON");
+    assert_eq!(message["jsonrpc"], "2.0");
+    Some(message)
+}
+
+/// `commit-lsp run` in an empty git repository, talking to the test like an editor.
+struct Session {
+    repo: PathBuf,
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Session {
+    /// Start the server and initialize it.
+    fn start(name: &str) -> Self {
+        let repo = std::env::temp_dir().join(format!("commit-lsp-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&repo).unwrap();
+        let status = Command::new("git")
+            .args(["init", "--quiet"])
+            .current_dir(&repo)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let mut child = Command::new(env!("CARGO_BIN_EXE_commit-lsp"))
+            .arg("run")
+            .current_dir(&repo)
+            // no user configuration, so no issue tracker is contacted
+            .env("XDG_CONFIG_HOME", repo.join("config"))
+            .env("COMMIT_LSP_LOG", "trace")
+            .env_remove("COMMIT_LSP_LOG_FILE")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let mut session = Self {
+            repo,
+            stdin: child.stdin.take().unwrap(),
+            stdout: BufReader::new(child.stdout.take().unwrap()),
+            child,
+        };
+
+        session.send(json!({
+            "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"capabilities": {}}
+        }));
+        let response = session.next().unwrap();
+        assert!(response["result"]["capabilities"].is_object());
+
+        session.notify("initialized", json!({}));
+        while session.next().unwrap()["method"] != "client/registerCapability" {}
+        session
+    }
+
+    fn send(&mut self, body: Value) {
+        self.stdin.write_all(&message(body)).unwrap();
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({"jsonrpc": "2.0", "method": method, "params": params}));
+    }
+
+    /// Read the next message of the server.
+    ///
+    /// Like an editor, requests of the server (e.g. to watch the config file) are answered, the
+    /// server does not stop while they are pending.
+    fn next(&mut self) -> Option<Value> {
+        let message = read_message(&mut self.stdout)?;
+        if message["id"].is_number() && message["method"].is_string() {
+            self.send(json!({"jsonrpc": "2.0", "id": message["id"], "result": null}));
+        }
+        Some(message)
+    }
+
+    /// Stop the server and return the messages it wrote until then.
+    fn finish(mut self) -> Vec<Value> {
+        self.send(json!({"jsonrpc": "2.0", "id": 2, "method": "shutdown"}));
+        self.notify("exit", Value::Null);
+        let Self {
+            repo,
+            mut child,
+            stdin,
+            mut stdout,
+        } = self;
+        // the server only stops once its input is closed
+        drop(stdin);
+        let mut messages = Vec::new();
+        while let Some(message) = read_message(&mut stdout) {
+            messages.push(message);
+        }
+
+        child.wait().unwrap();
+        std::fs::remove_dir_all(&repo).unwrap();
+        messages
+    }
+}
+
+fn open(session: &mut Session, uri: &str, text: &str) {
+    session.notify(
+        "textDocument/didOpen",
+        json!({"textDocument": {
+            "uri": uri, "languageId": "gitcommit", "version": 1, "text": text
+        }}),
+    );
+}
+
+/// `commit-lsp run` must not write anything to stdout except protocol messages, even with
+/// verbose logging.
+#[test]
+fn run_writes_only_protocol_to_stdout() {
+    let mut session = Session::start("stdout");
+
+    open(
+        &mut session,
+        "file:///tmp/COMMIT_EDITMSG",
+        "fix: thing.\nbody",
+    );
+    while session.next().unwrap()["method"] != "textDocument/publishDiagnostics" {}
+
+    session.finish();
+}
+
+#[test]
+fn edits_of_documents_are_published_independently() {
+    let mut session = Session::start("documents");
+    let uris = ["file:///a/COMMIT_EDITMSG", "file:///b/COMMIT_EDITMSG"];
+    for uri in uris {
+        open(&mut session, uri, "fix: thing");
+    }
+    let mut opened = 0;
+    while opened < uris.len() {
+        if session.next().unwrap()["method"] == "textDocument/publishDiagnostics" {
+            opened += 1;
+        }
+    }
+
+    // edit both documents within the cooldown of the diagnostics
+    for uri in uris {
+        session.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": {"uri": uri, "version": 2},
+                "contentChanges": [{"text": "fix: thing."}]
+            }),
+        );
+    }
+    std::thread::sleep(Duration::from_millis(500));
+
+    let messages = session.finish();
+    for uri in uris {
+        assert!(
+            messages
+                .iter()
+                .any(|m| m["method"] == "textDocument/publishDiagnostics"
+                    && m["params"]["uri"] == uri
+                    && m["params"]["diagnostics"][0]["code"] == "subject-trailing-period"),
+            "No diagnostics published for {uri}"
+        );
+    }
+}